@@ -1,18 +1,73 @@
-use log::trace;
+use std::path::Path;
 
-use crate::lib::consumer::{types::ConsumerState, Consumer, ConsumerOffsetConfiguration};
+use log::{debug, trace};
+use tauri::AppHandle;
+use tokio::sync::broadcast::error::RecvError;
 
-use super::{error::Result, types::GetPageResponse, AppState};
+use crate::lib::{
+    configuration::TopicSettings,
+    consumer::{
+        types::{ConsumerEvent, ConsumerState},
+        Consumer, ConsumerOffsetConfiguration, ConsumerStopConfiguration,
+    },
+    record_store::{ConsumerSession, ExportFormat, RecordExporter},
+    Error,
+};
+
+use super::{
+    error::Result,
+    notification::{notify_consumer_event, notify_error, notify_export_progress},
+    types::GetPageResponse,
+    AppState,
+};
 
 #[tauri::command]
 pub async fn start_consumer(
     cluster_id: &str,
     topic: &str,
     offset_config: ConsumerOffsetConfiguration,
+    stop_config: Option<ConsumerStopConfiguration>,
+    topic_settings: Option<TopicSettings>,
+    resume: Option<bool>,
     state: tauri::State<'_, AppState>,
+    app: AppHandle,
 ) -> Result<()> {
-    let consumer = state.get_cluster(cluster_id).await.get_consumer(topic).await;
-    Ok(consumer.start(&offset_config).await?)
+    let cluster = state.get_cluster(cluster_id).await;
+    // fallback to the settings stored in the cluster configuration
+    let topic_settings = topic_settings
+        .or_else(|| cluster.config.topic_settings.get(topic).cloned())
+        .unwrap_or_default();
+    let consumer = cluster.get_consumer(topic).await;
+    let mut events = consumer.subscribe();
+    consumer
+        .start(
+            &offset_config,
+            &stop_config.unwrap_or_default(),
+            &topic_settings,
+            resume.unwrap_or(false),
+        )
+        .await?;
+    // forward the consumer events to the frontend until the consumer stops
+    let (cluster_id, topic) = (cluster_id.to_string(), topic.to_string());
+    tauri::async_runtime::spawn(async move {
+        loop {
+            match events.recv().await {
+                Ok(event) => {
+                    if let ConsumerEvent::Error { message } = &event {
+                        notify_error("Kafka Consumer error", message, &app);
+                    }
+                    let is_stopped = matches!(event, ConsumerEvent::Stopped);
+                    notify_consumer_event(&cluster_id, &topic, event, &app);
+                    if is_stopped {
+                        break;
+                    }
+                }
+                Err(RecvError::Lagged(count)) => debug!("Skipped {} consumer events", count),
+                Err(RecvError::Closed) => break,
+            }
+        }
+    });
+    Ok(())
 }
 
 #[tauri::command]
@@ -57,3 +112,77 @@ pub async fn get_records_page(
         prev_page: if page_number >= 1 { Some(page_number - 1) } else { None },
     })
 }
+
+// full text search over the key, the payload and the header values of the consumed records
+#[tauri::command]
+pub async fn search_records(
+    cluster_id: &str,
+    topic: &str,
+    page_number: usize,
+    search: &str,
+    state: tauri::State<'_, AppState>,
+) -> Result<GetPageResponse> {
+    trace!("Search records page");
+    const PAGE_SIZE: usize = 100;
+    let cluster = state.get_cluster(cluster_id).await;
+    let consumer = cluster.get_consumer(topic).await;
+    // one more record to know if there is a next page
+    let mut records = consumer
+        .topic_store
+        .search_records(search, (page_number * PAGE_SIZE) as i64, PAGE_SIZE as i64 + 1)
+        .await?;
+    let has_next_page = records.len() > PAGE_SIZE;
+    records.truncate(PAGE_SIZE);
+    Ok(GetPageResponse {
+        records,
+        next_page: if has_next_page { Some(page_number + 1) } else { None },
+        prev_page: if page_number >= 1 { Some(page_number - 1) } else { None },
+    })
+}
+
+// write the records matching the query to a file, returns the number of exported records
+#[tauri::command]
+pub async fn export_records(
+    cluster_id: &str,
+    topic: &str,
+    query: Option<&str>,
+    format: ExportFormat,
+    output_path: &str,
+    state: tauri::State<'_, AppState>,
+    app: AppHandle,
+) -> Result<usize> {
+    let cluster = state.get_cluster(cluster_id).await;
+    let consumer = cluster.get_consumer(topic).await;
+    let exporter = RecordExporter::new(cluster.schema_registry_client.clone());
+    // the progress is notified from the blocking task writing the file
+    let on_progress = {
+        let (cluster_id, topic, output_path) = (cluster_id.to_string(), topic.to_string(), output_path.to_string());
+        move |record_count| notify_export_progress(&cluster_id, &topic, &output_path, record_count, &app)
+    };
+    Ok(exporter
+        .export(
+            consumer.topic_store.clone(),
+            query,
+            &format,
+            Path::new(output_path),
+            on_progress,
+        )
+        .await?)
+}
+
+#[tauri::command]
+pub async fn list_sessions(state: tauri::State<'_, AppState>) -> Result<Vec<ConsumerSession>> {
+    Ok(state.get_app_store().await.list_sessions().await?)
+}
+
+#[tauri::command]
+pub async fn delete_session(cluster_id: &str, topic: &str, state: tauri::State<'_, AppState>) -> Result<()> {
+    let consumer = state.get_cluster(cluster_id).await.get_consumer(topic).await;
+    if consumer.get_consumer_state().await?.is_running {
+        return Err(Error::Consumer {
+            message: format!("Stop the consumer of {} before deleting the session", topic),
+        }
+        .into());
+    }
+    Ok(state.get_app_store().await.delete_session(cluster_id, topic).await?)
+}
@@ -7,21 +7,32 @@ pub struct TauriError {
     #[serde(rename = "errorType")]
     pub error_type: String,
     pub message: String,
+    // position of the error in the user query
+    #[serde(skip_serializing_if = "Option::is_none", default)]
+    pub position: Option<usize>,
 }
 
 impl From<Error> for TauriError {
     fn from(err: Error) -> Self {
+        let position = match &err {
+            Error::InvalidQuery { position, .. } => *position,
+            _ => None,
+        };
         let (error_type, message) = match err {
             Error::AvroParse { message } => ("Avro parser error", message),
+            Error::ProtobufParse { message } => ("Protobuf parser error", message),
+            Error::JsonSchemaParse { message } => ("JSON Schema parser error", message),
             Error::IO { message } => ("IO error", message),
             Error::JSONSerde { message } => ("JSON Serde error", message),
             Error::Consumer { message } => ("Kafka Consumer error", message),
             Error::Kafka { message } => ("Kafka error", message),
             Error::SqlError { message } => ("SQLite error", message),
+            Error::InvalidQuery { message, .. } => ("Invalid query", message),
         };
         TauriError {
             error_type: error_type.into(),
             message,
+            position,
         }
     }
 }
@@ -35,6 +46,7 @@ impl From<SchemaRegistryError> for TauriError {
                 SchemaRegistryError::InvalidUrl => "Invalid url".into(),
                 SchemaRegistryError::SchemaParsing { message: msg } => msg,
             },
+            position: None,
         }
     }
 }
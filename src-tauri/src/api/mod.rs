@@ -3,9 +3,9 @@ pub mod configuration;
 pub mod consumer;
 mod error;
 mod notification;
+pub mod producer;
 pub mod schema_registry;
 mod state;
 mod types;
 
-// pub use notification::notify_error;
 pub use state::AppState;
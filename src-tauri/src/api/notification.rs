@@ -1,14 +1,47 @@
 use tauri::{AppHandle, Manager};
 
-use super::error::TauriError;
+use crate::lib::consumer::types::ConsumerEvent;
 
-pub fn _notify_error(error_type: &str, message: &str, app: &AppHandle) {
+use super::{
+    error::TauriError,
+    types::{ConsumerNotification, ExportNotification},
+};
+
+pub fn notify_error(error_type: &str, message: &str, app: &AppHandle) {
     app.app_handle()
         .emit_all(
             "error",
             TauriError {
                 error_type: error_type.to_string(),
                 message: message.to_string(),
+                position: None,
+            },
+        )
+        .expect("unable to send a notification to the frontend");
+}
+
+pub fn notify_consumer_event(cluster_id: &str, topic: &str, event: ConsumerEvent, app: &AppHandle) {
+    app.app_handle()
+        .emit_all(
+            "consumer",
+            ConsumerNotification {
+                cluster_id: cluster_id.to_string(),
+                topic: topic.to_string(),
+                event,
+            },
+        )
+        .expect("unable to send a notification to the frontend");
+}
+
+pub fn notify_export_progress(cluster_id: &str, topic: &str, output_path: &str, record_count: usize, app: &AppHandle) {
+    app.app_handle()
+        .emit_all(
+            "export",
+            ExportNotification {
+                cluster_id: cluster_id.to_string(),
+                topic: topic.to_string(),
+                output_path: output_path.to_string(),
+                record_count,
             },
         )
         .expect("unable to send a notification to the frontend");
@@ -0,0 +1,60 @@
+use std::path::Path;
+
+use log::debug;
+
+use crate::lib::producer::{
+    import_records as import, ImportFormat, ImportResult, Producer, ProducerMode, ProducerRecord,
+};
+
+use super::{error::Result, AppState};
+
+#[tauri::command]
+pub async fn produce_record(
+    cluster_id: &str,
+    topic: &str,
+    key: Option<&str>,
+    payload: Option<&str>,
+    partition: Option<i32>,
+    timestamp: Option<i64>,
+    mode: Option<ProducerMode>,
+    state: tauri::State<'_, AppState>,
+) -> Result<()> {
+    debug!("Produce record to topic {}", topic);
+    let cluster = state.get_cluster(cluster_id).await;
+    Ok(cluster
+        .producer
+        .produce_record(
+            &ProducerRecord {
+                topic: topic.into(),
+                key: key.map(|k| k.into()),
+                payload: payload.map(|p| p.into()),
+                headers: vec![],
+                partition,
+                timestamp,
+            },
+            &mode.unwrap_or(ProducerMode::String),
+        )
+        .await?)
+}
+
+// produce the records stored in a jsonl or csv file to the topic
+#[tauri::command]
+pub async fn import_records(
+    cluster_id: &str,
+    topic: &str,
+    file_path: &str,
+    format: ImportFormat,
+    mode: Option<ProducerMode>,
+    state: tauri::State<'_, AppState>,
+) -> Result<ImportResult> {
+    debug!("Import records from {} to topic {}", file_path, topic);
+    let cluster = state.get_cluster(cluster_id).await;
+    Ok(import(
+        cluster.producer.as_ref(),
+        topic,
+        Path::new(file_path),
+        &format,
+        &mode.unwrap_or(ProducerMode::String),
+    )
+    .await?)
+}
@@ -12,6 +12,7 @@ pub async fn list_subjects(cluster_id: &str, state: tauri::State<'_, AppState>)
     let client = state.get_schema_reg_client(cluster_id).await.ok_or(TauriError {
         error_type: "Configuration error".into(),
         message: "Missing schema registry configuration".into(),
+        position: None,
     })?;
     Ok(client.list_subjects().await?)
 }
@@ -22,6 +23,7 @@ pub async fn get_subject(subject_name: &str, cluster_id: &str, state: tauri::Sta
     let client = state.get_schema_reg_client(cluster_id).await.ok_or(TauriError {
         error_type: "Configuration error".into(),
         message: "Missing schema registry configuration".into(),
+        position: None,
     })?;
     Ok(client.get_subject(subject_name).await?)
 }
@@ -32,6 +34,7 @@ pub async fn delete_subject(subject_name: &str, cluster_id: &str, state: tauri::
     let client = state.get_schema_reg_client(cluster_id).await.ok_or(TauriError {
         error_type: "Configuration error".into(),
         message: "Missing schema registry configuration".into(),
+        position: None,
     })?;
     Ok(client.delete_subject(subject_name).await?)
 }
@@ -47,6 +50,7 @@ pub async fn delete_subject_version(
     let client = state.get_schema_reg_client(cluster_id).await.ok_or(TauriError {
         error_type: "Configuration error".into(),
         message: "Missing schema registry configuration".into(),
+        position: None,
     })?;
     Ok(client.delete_version(subject_name, version).await?)
 }
@@ -1,15 +1,17 @@
 use std::{collections::HashMap, sync::Arc};
 
+use dirs::data_dir;
 use futures::lock::Mutex;
-use log::debug;
+use log::{debug, error};
 
-use crate::lib::{configuration::ConfigStore, schema_registry::CachedSchemaRegistry, Cluster};
+use crate::lib::{configuration::ConfigStore, record_store::AppStore, schema_registry::CachedSchemaRegistry, Cluster};
 
 type ClusterId = String;
 
 #[derive(Default)]
 pub struct AppState {
     clusters: Arc<Mutex<HashMap<ClusterId, Arc<Cluster>>>>,
+    app_store: Arc<Mutex<Option<Arc<AppStore>>>>,
 }
 
 impl AppState {
@@ -18,7 +20,7 @@ impl AppState {
         let mut map = clusters.lock().await;
         if map.get(cluster_id).is_none() {
             debug!("Init cluster {}", cluster_id);
-            let cluster = AppState::build_new_cluster(cluster_id);
+            let cluster = AppState::build_new_cluster(cluster_id, self.get_app_store().await);
             map.insert(cluster_id.into(), Arc::new(cluster));
         }
         map.get(cluster_id)
@@ -31,7 +33,38 @@ impl AppState {
         cluster.schema_registry_client.as_ref().cloned()
     }
 
-    fn build_new_cluster(cluster_id: &str) -> Cluster {
+    // store shared by all the clusters, the tables are namespaced by cluster id
+    pub async fn get_app_store(&self) -> Arc<AppStore> {
+        let mut app_store = self.app_store.lock().await;
+        if app_store.is_none() {
+            *app_store = Some(Arc::new(AppState::build_app_store()));
+        }
+        app_store.as_ref().expect("The app store must be initialized").clone()
+    }
+
+    fn build_app_store() -> AppStore {
+        let configurations = ConfigStore::new()
+            .get_configuration()
+            .expect("Unable to get the configuration");
+        let app_store = if configurations.persist_records.unwrap_or(false) {
+            let mut store_path = data_dir().expect("Unable to retrieve the data directory");
+            store_path.push("com.insulator");
+            store_path.push("records.db");
+            debug!("Open the record store at {:?}", store_path);
+            AppStore::from_path(&store_path).unwrap_or_else(|err| {
+                error!(
+                    "Unable to open the record store, fallback to the in memory store: {}",
+                    err
+                );
+                AppStore::new()
+            })
+        } else {
+            AppStore::new()
+        };
+        app_store.with_limits(configurations.topic_store_limits, configurations.global_store_limits)
+    }
+
+    fn build_new_cluster(cluster_id: &str, app_store: Arc<AppStore>) -> Cluster {
         debug!("Init cluster {}", cluster_id);
         let configurations = ConfigStore::new()
             .get_configuration()
@@ -41,6 +74,6 @@ impl AppState {
             .iter()
             .find(|c| c.id == cluster_id)
             .expect("Unable to find the cluster config");
-        Cluster::new(cluster_config)
+        Cluster::new(cluster_config, app_store)
     }
 }
@@ -1,6 +1,6 @@
 use serde::Serialize;
 
-use crate::lib::types::ParsedKafkaRecord;
+use crate::lib::{consumer::types::ConsumerEvent, types::ParsedKafkaRecord};
 
 #[derive(Serialize, Debug)]
 pub struct GetPageResponse {
@@ -10,3 +10,22 @@ pub struct GetPageResponse {
     #[serde(rename = "prevPage")]
     pub prev_page: Option<usize>,
 }
+
+#[derive(Serialize, Debug, Clone)]
+pub struct ConsumerNotification {
+    #[serde(rename = "clusterId")]
+    pub cluster_id: String,
+    pub topic: String,
+    pub event: ConsumerEvent,
+}
+
+#[derive(Serialize, Debug, Clone)]
+pub struct ExportNotification {
+    #[serde(rename = "clusterId")]
+    pub cluster_id: String,
+    pub topic: String,
+    #[serde(rename = "outputPath")]
+    pub output_path: String,
+    #[serde(rename = "recordCount")]
+    pub record_count: usize,
+}
@@ -8,6 +8,7 @@ use crate::lib::{
     configuration::ClusterConfig,
     consumer::{Consumer, KafkaConsumer},
     parser::{Parser, RecordParser},
+    producer::{KafkaProducer, Producer},
     record_store::TopicStore,
     schema_registry::{CachedSchemaRegistry, SchemaRegistryClient},
 };
@@ -16,16 +17,18 @@ use super::record_store::AppStore;
 
 type TopicName = String;
 
-pub struct Cluster<SR = CachedSchemaRegistry, C = KafkaConsumer, P = RecordParser, A = KafkaAdmin>
+pub struct Cluster<SR = CachedSchemaRegistry, C = KafkaConsumer, P = RecordParser, A = KafkaAdmin, PR = KafkaProducer>
 where
     SR: SchemaRegistryClient + Send + Sync,
     C: Consumer + Send + Sync,
     P: Parser + Send + Sync,
     A: Admin + Send + Sync,
+    PR: Producer + Send + Sync,
 {
     pub config: ClusterConfig,
     pub schema_registry_client: Option<Arc<SR>>,
     pub admin_client: Arc<A>,
+    pub producer: Arc<PR>,
     pub parser: Arc<P>,
     pub app_store: Arc<AppStore>,
     consumers: Arc<Mutex<HashMap<TopicName, Arc<C>>>>,
@@ -38,6 +41,7 @@ impl Clone for Cluster<CachedSchemaRegistry> {
             schema_registry_client: self.schema_registry_client.clone(),
             consumers: self.consumers.clone(),
             admin_client: self.admin_client.clone(),
+            producer: self.producer.clone(),
             parser: self.parser.clone(),
             app_store: self.app_store.clone(),
         }
@@ -45,7 +49,7 @@ impl Clone for Cluster<CachedSchemaRegistry> {
 }
 
 impl Cluster {
-    pub fn new(config: &ClusterConfig) -> Self {
+    pub fn new(config: &ClusterConfig, app_store: Arc<AppStore>) -> Self {
         let (schema_registry_client, parser) = {
             if let Some(s_config) = &config.schema_registry {
                 let ptr = Arc::new(CachedSchemaRegistry::new(
@@ -60,11 +64,12 @@ impl Cluster {
         };
         Cluster {
             config: config.clone(),
+            producer: Arc::new(KafkaProducer::new(config, schema_registry_client.clone())),
             schema_registry_client,
             consumers: Arc::new(Mutex::new(HashMap::new())),
             admin_client: Arc::new(KafkaAdmin::new(config)),
             parser: Arc::new(parser),
-            app_store: Arc::new(AppStore::new()),
+            app_store,
         }
     }
 
@@ -1,5 +1,9 @@
+use std::collections::HashMap;
+
 use serde::{Deserialize, Serialize};
 
+use crate::lib::parser::{AvroEncoding, AvroReaderSchema, ParserMode};
+
 #[derive(Serialize, Deserialize, Debug, Default, PartialEq, Eq)]
 pub struct InsulatorConfig {
     pub clusters: Vec<ClusterConfig>,
@@ -8,6 +12,28 @@ pub struct InsulatorConfig {
     pub show_notifications: Option<bool>,
     #[serde(rename = "useRegex")]
     pub use_regex: Option<bool>,
+    // store the consumed records on disk instead of in memory
+    #[serde(rename = "persistRecords")]
+    pub persist_records: Option<bool>,
+    // limits of the records stored consuming each topic
+    #[serde(rename = "topicStoreLimits", default)]
+    pub topic_store_limits: StoreLimits,
+    // limits of the records stored consuming all the topics
+    #[serde(rename = "globalStoreLimits", default)]
+    pub global_store_limits: StoreLimits,
+}
+
+// the oldest records are evicted when a limit is exceeded, no limit if None
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct StoreLimits {
+    #[serde(rename = "maxRecords")]
+    pub max_records: Option<usize>,
+    // size of the decoded keys, payloads and headers, plus the raw keys and payloads
+    #[serde(rename = "maxBytes")]
+    pub max_bytes: Option<usize>,
+    // compared to the record timestamp
+    #[serde(rename = "maxAge")]
+    pub max_age: Option<i64>, //time in ms
 }
 
 #[derive(Serialize, Deserialize, Debug, PartialEq, Eq)]
@@ -24,6 +50,26 @@ pub struct ClusterConfig {
     pub authentication: AuthenticationConfig,
     #[serde(rename = "schemaRegistry")]
     pub schema_registry: Option<SchemaRegistryConfig>,
+    #[serde(rename = "topicSettings", default)]
+    pub topic_settings: HashMap<String, TopicSettings>,
+}
+
+// format used to decode the records of a topic
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq, Eq)]
+pub struct TopicSettings {
+    #[serde(rename = "keyMode", default)]
+    pub key_mode: ParserMode,
+    #[serde(rename = "payloadMode", default)]
+    pub payload_mode: ParserMode,
+    #[serde(rename = "avroEncoding", default)]
+    pub avro_encoding: AvroEncoding,
+    #[serde(rename = "keyReaderSchema", default)]
+    pub key_reader_schema: Option<AvroReaderSchema>,
+    #[serde(rename = "payloadReaderSchema", default)]
+    pub payload_reader_schema: Option<AvroReaderSchema>,
+    // json paths of the payload exposed as columns of the topic table, by column name
+    #[serde(default)]
+    pub projections: HashMap<String, String>,
 }
 
 #[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq, Eq)]
@@ -1,25 +1,48 @@
-use std::{sync::Arc, time::Duration};
+use std::{
+    collections::HashMap,
+    sync::Arc,
+    time::{Duration, Instant, SystemTime, UNIX_EPOCH},
+};
 
 use crate::lib::{
-    configuration::{build_kafka_client_config, ClusterConfig},
-    consumer::types::{ConsumerOffsetConfiguration, ConsumerState},
+    configuration::{build_kafka_client_config, ClusterConfig, TopicSettings},
+    consumer::{
+        pipeline::RecordPipeline,
+        stop_condition::StopCondition,
+        types::{
+            ConsumerEvent, ConsumerOffsetConfiguration, ConsumerProgress, ConsumerState, ConsumerStopConfiguration,
+            PartitionProgress,
+        },
+    },
     error::{Error, Result},
     record_store::TopicStore,
-    types::RawKafkaRecord,
+    types::{RawKafkaRecord, RecordHeader},
 };
 use async_trait::async_trait;
 use futures::lock::Mutex;
 use log::{debug, error, trace, warn};
 use rdkafka::{
     consumer::{BaseConsumer, Consumer as ApacheKafkaConsumer},
-    message::OwnedMessage,
+    message::{Header, Headers, OwnedMessage},
     Message, Offset, TopicPartitionList,
 };
 use tauri::async_runtime::JoinHandle;
+use tokio::sync::broadcast;
+
+// interval between two progress events
+const PROGRESS_INTERVAL: Duration = Duration::from_secs(1);
+// interval between two fetches of the watermarks of a partition, used to compute the lag
+const WATERMARKS_INTERVAL: Duration = Duration::from_secs(30);
 
 #[async_trait]
 pub trait Consumer {
-    async fn start(&self, offset_config: &ConsumerOffsetConfiguration) -> Result<()>;
+    async fn start(
+        &self,
+        offset_config: &ConsumerOffsetConfiguration,
+        stop_config: &ConsumerStopConfiguration,
+        topic_settings: &TopicSettings,
+        resume: bool,
+    ) -> Result<()>;
     async fn stop(&self) -> Result<()>;
     async fn get_consumer_state(&self) -> Result<ConsumerState>;
 }
@@ -29,6 +52,8 @@ pub struct KafkaConsumer {
     topic: String,
     loop_handle: Arc<Mutex<Option<JoinHandle<()>>>>,
     is_running: Arc<Mutex<bool>>,
+    is_finished: Arc<Mutex<bool>>,
+    events: broadcast::Sender<ConsumerEvent>,
     pub topic_store: Arc<TopicStore>,
 }
 
@@ -40,13 +65,26 @@ impl KafkaConsumer {
             loop_handle: Arc::new(Mutex::new(None)),
             topic_store: Arc::new(topic_store),
             is_running: Arc::new(Mutex::new(false)),
+            is_finished: Arc::new(Mutex::new(false)),
+            events: broadcast::channel(100).0,
         }
     }
+
+    // receive the consumer events (errors, start/stop and progress)
+    pub fn subscribe(&self) -> broadcast::Receiver<ConsumerEvent> {
+        self.events.subscribe()
+    }
 }
 
 #[async_trait]
 impl Consumer for KafkaConsumer {
-    async fn start(&self, offset_config: &ConsumerOffsetConfiguration) -> Result<()> {
+    async fn start(
+        &self,
+        offset_config: &ConsumerOffsetConfiguration,
+        stop_config: &ConsumerStopConfiguration,
+        topic_settings: &TopicSettings,
+        resume: bool,
+    ) -> Result<()> {
         let topic = self.topic.clone();
         if self.loop_handle.lock().await.is_some() {
             warn!("Try to start an already running consumer");
@@ -54,6 +92,8 @@ impl Consumer for KafkaConsumer {
                 message: format!("A consumer is already running for {}", topic),
             });
         }
+        // update the projections before the records are stored, reporting the invalid ones
+        self.topic_store.set_projections(&topic_settings.projections).await?;
         // set the handle to the consumer loop
         *self.loop_handle.clone().lock().await = Some(tauri::async_runtime::spawn({
             // clone arcs for the closure below
@@ -64,36 +104,136 @@ impl Consumer for KafkaConsumer {
             let handle = self.loop_handle.clone();
             let topic_store = self.topic_store.clone();
             let offset_config = offset_config.clone();
+            let stop_config = stop_config.clone();
+            let topic_settings = topic_settings.clone();
             let is_running = self.is_running.clone();
+            let is_finished = self.is_finished.clone();
+            let events = self.events.clone();
             *is_running.lock().await = true;
+            *is_finished.lock().await = false;
             async move {
-                KafkaConsumer::setup_consumer(&consumer, &[&topic], &offset_config)
-                    .await
-                    .expect("Unable to setup the consumer");
-                // clear the store before starting the loop
-                topic_store.clear().await.expect("Unable to clear the table");
-                // infinite consumer loop
+                let setup = match KafkaConsumer::setup_consumer(&consumer, &[&topic], &offset_config).await {
+                    Ok(_) if resume => KafkaConsumer::resume_from_store(&consumer, &topic, &topic_store).await,
+                    res => res,
+                }
+                .and_then(|_| KafkaConsumer::build_stop_condition(&consumer, &topic, &offset_config, &stop_config));
+                let mut stop_condition = match setup {
+                    Ok(stop_condition) => stop_condition,
+                    Err(err) => {
+                        error!("Unable to setup the consumer: {}", err);
+                        let _ = events.send(ConsumerEvent::Error {
+                            message: format!("Unable to setup the consumer for {}\n{}", topic, err),
+                        });
+                        KafkaConsumer::_stop(handle.clone(), &events)
+                            .await
+                            .expect("Unable to stop the consumer");
+                        return;
+                    }
+                };
+                // clear the store before starting the loop, unless the new records are appended
+                if !resume {
+                    topic_store.clear().await.expect("Unable to clear the table");
+                }
+                let started_at = SystemTime::now()
+                    .duration_since(UNIX_EPOCH)
+                    .map(|d| d.as_millis() as i64)
+                    .unwrap_or_default();
+                if let Err(err) = topic_store.save_session(&offset_config, started_at).await {
+                    warn!("Unable to save the consumer session: {}", err);
+                }
+                let _ = events.send(ConsumerEvent::Started);
+                // last consumed offset in each partition
+                let mut offsets = HashMap::new();
+                let mut watermarks = HashMap::new();
+                let mut record_count = 0;
+                let mut last_progress = Instant::now();
+                // the records are decoded and stored out of the poll loop
+                let mut pipeline = RecordPipeline::start(topic_store.clone(), &topic_settings);
+                let mut is_paused = false;
+                // consumer loop, infinite unless a stop configuration is specified
                 debug!("Start consumer loop");
                 while *is_running.lock().await {
+                    // pause the partitions while the store is falling behind
+                    let should_pause = if is_paused {
+                        !pipeline.is_drained()
+                    } else {
+                        !pipeline.has_capacity()
+                    };
+                    if should_pause != is_paused {
+                        is_paused = should_pause;
+                        trace!("Paused {}: {}", topic, is_paused);
+                        let res = consumer.assignment().and_then(|assignment| {
+                            if is_paused {
+                                consumer.pause(&assignment)
+                            } else {
+                                consumer.resume(&assignment)
+                            }
+                        });
+                        if let Err(err) = res {
+                            warn!("Unable to pause or resume the partitions of {}: {}", topic, err);
+                        }
+                    }
+                    for warning in pipeline.take_warnings() {
+                        let _ = events.send(ConsumerEvent::Error {
+                            message: format!("{}\n{}", topic, warning),
+                        });
+                    }
+                    if last_progress.elapsed() >= PROGRESS_INTERVAL {
+                        last_progress = Instant::now();
+                        // the poll may never be idle while the other partitions receive records
+                        KafkaConsumer::check_positions(&consumer, &topic, &mut stop_condition);
+                        let progress =
+                            KafkaConsumer::get_progress(&consumer, &topic, &offsets, &mut watermarks, record_count);
+                        let _ = events.send(ConsumerEvent::Progress(progress));
+                    }
+                    if stop_condition.is_completed() {
+                        debug!("Consumer reached the end of the configured range");
+                        KafkaConsumer::close_pipeline(&mut pipeline, &topic, &events).await;
+                        *is_finished.lock().await = true;
+                        let progress =
+                            KafkaConsumer::get_progress(&consumer, &topic, &offsets, &mut watermarks, record_count);
+                        let _ = events.send(ConsumerEvent::Progress(progress));
+                        KafkaConsumer::_stop(handle.clone(), &events)
+                            .await
+                            .expect("Unable to stop the consumer");
+                        break;
+                    }
                     match consumer.poll(Duration::from_millis(200)) {
                         Some(Ok(msg)) => {
                             trace!("New record from {}", topic);
-                            topic_store
-                                .insert_record(&KafkaConsumer::map_kafka_record(&msg.detach()))
-                                .await
-                                .expect("Unable to insert the new record in store");
+                            let record = KafkaConsumer::map_kafka_record(&msg.detach());
+                            offsets.insert(record.partition, record.offset);
+                            if stop_condition.check(record.partition, record.offset, record.timestamp) {
+                                if let Err(err) = pipeline.push(record).await {
+                                    error!("Unable to store the records from {}: {}", topic, err);
+                                    let _ = events.send(ConsumerEvent::Error {
+                                        message: format!("Unable to store the records from {}\n{}", topic, err),
+                                    });
+                                    KafkaConsumer::close_pipeline(&mut pipeline, &topic, &events).await;
+                                    KafkaConsumer::_stop(handle.clone(), &events)
+                                        .await
+                                        .expect("Unable to stop the consumer");
+                                    break;
+                                }
+                                record_count += 1;
+                            }
                         }
                         Some(Err(err)) => {
                             error!("An error occurs consuming from kafka: {}", err);
-                            //todo: self.notify_error("Consumer error", &err.to_string());
-                            KafkaConsumer::_stop(handle.clone())
+                            KafkaConsumer::close_pipeline(&mut pipeline, &topic, &events).await;
+                            let _ = events.send(ConsumerEvent::Error {
+                                message: format!("An error occurs consuming from {}\n{}", topic, err),
+                            });
+                            KafkaConsumer::_stop(handle.clone(), &events)
                                 .await
                                 .expect("Unable to stop the consumer");
                             break;
                         }
-                        None => (),
+                        None => KafkaConsumer::check_positions(&consumer, &topic, &mut stop_condition),
                     }
                 }
+                // stopped by the user
+                KafkaConsumer::close_pipeline(&mut pipeline, &topic, &events).await;
             }
         }));
         Ok(())
@@ -101,27 +241,96 @@ impl Consumer for KafkaConsumer {
 
     async fn stop(&self) -> Result<()> {
         *self.is_running.lock().await = false;
-        KafkaConsumer::_stop(self.loop_handle.clone()).await
+        // wait for the loop to store the records in the pipeline instead of aborting it
+        let handle = self.loop_handle.lock().await.take();
+        if let Some(handle) = handle {
+            if let Err(err) = handle.await {
+                error!("The consumer loop of {} failed: {}", self.topic, err);
+            }
+            debug!("Consumer stopped");
+            let _ = self.events.send(ConsumerEvent::Stopped);
+        }
+        Ok(())
     }
 
     async fn get_consumer_state(&self) -> Result<ConsumerState> {
+        let (topic_limits, global_limits) = self.topic_store.get_limits();
         Ok(ConsumerState {
             is_running: self.loop_handle.clone().lock().await.is_some(),
+            is_finished: *self.is_finished.lock().await,
             record_count: self.topic_store.get_size(None).await?, //total records in the topic
+            evicted_count: self.topic_store.get_evicted_count().await?,
+            topic_limits,
+            global_limits,
         })
     }
 }
 
 impl KafkaConsumer {
-    async fn _stop(loop_handle: Arc<Mutex<Option<JoinHandle<()>>>>) -> Result<()> {
+    // wait for the records in the pipeline to be stored, reporting the store errors
+    async fn close_pipeline(pipeline: &mut RecordPipeline, topic: &str, events: &broadcast::Sender<ConsumerEvent>) {
+        if let Err(err) = pipeline.close().await {
+            error!("Unable to store the records from {}: {}", topic, err);
+            let _ = events.send(ConsumerEvent::Error {
+                message: format!("Unable to store the records from {}\n{}", topic, err),
+            });
+        }
+    }
+
+    async fn _stop(
+        loop_handle: Arc<Mutex<Option<JoinHandle<()>>>>,
+        events: &broadcast::Sender<ConsumerEvent>,
+    ) -> Result<()> {
         debug!("Consumer stopped");
-        if let Some(handle) = &*loop_handle.lock().await {
+        if let Some(handle) = loop_handle.lock().await.take() {
             handle.abort();
+            // the send fails only if nobody is listening
+            let _ = events.send(ConsumerEvent::Stopped);
         }
-        *loop_handle.lock().await = None;
         Ok(())
     }
 
+    // fetching the watermarks blocks the consumer loop, so the high watermark of each
+    // partition is cached and fetched again only after WATERMARKS_INTERVAL
+    fn get_progress(
+        consumer: &BaseConsumer,
+        topic: &str,
+        offsets: &HashMap<i32, i64>,
+        watermarks: &mut HashMap<i32, (Option<i64>, Instant)>,
+        record_count: usize,
+    ) -> ConsumerProgress {
+        let tmo = Duration::from_secs(5);
+        let mut partitions: Vec<_> = offsets
+            .iter()
+            .map(|(partition, offset)| {
+                let high = match watermarks.get(partition) {
+                    Some((high, fetched_at)) if fetched_at.elapsed() < WATERMARKS_INTERVAL => *high,
+                    _ => {
+                        let high = consumer
+                            .fetch_watermarks(topic, *partition, tmo)
+                            .map(|(_, high)| high)
+                            .map_err(|err| warn!("Unable to fetch the watermarks for {}/{}: {}", topic, partition, err))
+                            .ok();
+                        // the failures are cached as well, to not retry on each progress event
+                        watermarks.insert(*partition, (high, Instant::now()));
+                        high
+                    }
+                };
+                PartitionProgress {
+                    partition: *partition,
+                    offset: *offset,
+                    // the high watermark is the offset of the next record produced
+                    lag: high.map(|high| (high - 1 - offset).max(0)).unwrap_or(-1),
+                }
+            })
+            .collect();
+        partitions.sort_by_key(|p| p.partition);
+        ConsumerProgress {
+            record_count,
+            partitions,
+        }
+    }
+
     fn map_kafka_record(msg: &OwnedMessage) -> RawKafkaRecord {
         RawKafkaRecord {
             payload: msg.payload().map(|v| v.to_owned()),
@@ -130,7 +339,117 @@ impl KafkaConsumer {
             partition: msg.partition(),
             offset: msg.offset(),
             timestamp: msg.timestamp().to_millis(),
+            headers: msg
+                .headers()
+                .map(|headers| headers.iter().map(KafkaConsumer::map_kafka_header).collect())
+                .unwrap_or_default(),
+        }
+    }
+
+    // the null values are kept, the values that are not valid utf-8 are base64 encoded
+    fn map_kafka_header(header: Header<&[u8]>) -> RecordHeader {
+        let (value, binary) = match header.value {
+            Some(value) => match std::str::from_utf8(value) {
+                Ok(text) => (Some(text.to_string()), false),
+                Err(_) => (Some(base64::encode(value)), true),
+            },
+            None => (None, false),
+        };
+        RecordHeader {
+            key: header.key.to_string(),
+            value,
+            binary,
+        }
+    }
+
+    // continue after the last record stored in each partition, the partitions
+    // without stored records start from the offset configuration
+    async fn resume_from_store(consumer: &BaseConsumer, topic: &str, topic_store: &TopicStore) -> Result<()> {
+        let mut assignment = consumer.assignment()?;
+        for (partition, offset) in topic_store.get_last_offsets().await? {
+            if assignment.find_partition(topic, partition).is_some() {
+                assignment.set_partition_offset(topic, partition, Offset::Offset(offset + 1))?;
+            }
+        }
+        trace!("Resume from {:?}", assignment);
+        consumer.assign(&assignment)?;
+        Ok(())
+    }
+
+    // complete the partitions that reached the end offset without returning the record before it
+    fn check_positions(consumer: &BaseConsumer, topic: &str, stop_condition: &mut StopCondition) {
+        match consumer.position() {
+            Ok(positions) => {
+                for tpo in positions.elements_for_topic(topic) {
+                    if let Offset::Offset(position) = tpo.offset() {
+                        stop_condition.check_position(tpo.partition(), position);
+                    }
+                }
+            }
+            Err(err) => warn!("Unable to get the position of the consumer of {}: {}", topic, err),
+        }
+    }
+
+    fn build_stop_condition(
+        consumer: &BaseConsumer,
+        topic: &str,
+        offset_config: &ConsumerOffsetConfiguration,
+        stop_config: &ConsumerStopConfiguration,
+    ) -> Result<StopCondition> {
+        let tmo = Duration::from_secs(60);
+        let stop_timestamp = match offset_config {
+            ConsumerOffsetConfiguration::Custom { stop_timestamp, .. } => *stop_timestamp,
+            _ => None,
+        };
+        let max_records = match stop_config {
+            ConsumerStopConfiguration::RecordCount(count) => Some(*count),
+            _ => None,
+        };
+        let mut start_offsets = HashMap::new();
+        let mut end_offsets = HashMap::new();
+        for tpo in consumer.assignment()?.elements_for_topic(topic) {
+            let (low, high) = consumer.fetch_watermarks(topic, tpo.partition(), tmo)?;
+            let start_offset = match tpo.offset() {
+                Offset::Offset(offset) => offset,
+                Offset::Beginning => low,
+                _ => high,
+            };
+            start_offsets.insert(tpo.partition(), start_offset);
+            if let ConsumerStopConfiguration::CurrentEnd = stop_config {
+                // the high watermark is the offset of the next record produced
+                end_offsets.insert(tpo.partition(), high);
+            }
         }
+        if let Some(stop_timestamp) = stop_timestamp {
+            // the records are consumed up to the first record produced after the stop timestamp
+            let mut timestamp_assignment = TopicPartitionList::new();
+            for partition in start_offsets.keys() {
+                timestamp_assignment.add_partition_offset(topic, *partition, Offset::Offset(stop_timestamp + 1))?;
+            }
+            let now = SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .map(|d| d.as_millis() as i64)
+                .unwrap_or(i64::MAX);
+            for tpo in consumer.offsets_for_times(timestamp_assignment, tmo)?.elements() {
+                let end_offset = match tpo.offset() {
+                    Offset::Offset(offset) => Some(offset),
+                    // no records after the stop timestamp yet
+                    _ if stop_timestamp <= now => Some(consumer.fetch_watermarks(topic, tpo.partition(), tmo)?.1),
+                    _ => None,
+                };
+                if let Some(end_offset) = end_offset {
+                    let current = end_offsets.entry(tpo.partition()).or_insert(end_offset);
+                    *current = (*current).min(end_offset);
+                }
+            }
+        }
+        trace!("Consume from {:?} to {:?}", start_offsets, end_offsets);
+        Ok(StopCondition::new(
+            start_offsets,
+            end_offsets,
+            stop_timestamp,
+            max_records,
+        ))
     }
 
     pub async fn setup_consumer(
@@ -138,6 +457,7 @@ impl KafkaConsumer {
         topics: &[&str],
         config: &ConsumerOffsetConfiguration,
     ) -> Result<()> {
+        Self::validate_offset_config(config)?;
         let tmo = Duration::from_secs(60);
         let metadata = consumer.fetch_metadata(None, tmo)?;
         let topic_partition: Vec<_> = metadata
@@ -184,7 +504,119 @@ impl KafkaConsumer {
                 trace!("Assign partitions {:?}", timestamp_assignment);
                 consumer.assign(&consumer.offsets_for_times(timestamp_assignment, tmo)?)?;
             }
+            ConsumerOffsetConfiguration::PartitionOffsets { offsets } => {
+                if let Some(partition) = offsets.keys().find(|p| !topic_partition.iter().any(|(_, id)| id == *p)) {
+                    return Err(Error::Consumer {
+                        message: format!("Partition {} not found in {}", partition, topics.join(", ")),
+                    });
+                }
+                let mut assignment = TopicPartitionList::new();
+                for (t, p) in topic_partition.iter().filter(|(_, p)| offsets.contains_key(p)) {
+                    assignment.add_partition_offset(t, *p, Offset::Offset(offsets[p]))?;
+                }
+                trace!("Assign partitions {:?}", assignment);
+                consumer.assign(&assignment)?;
+            }
+            ConsumerOffsetConfiguration::LastRecords { count } => {
+                // use explicit offsets instead of Offset::OffsetTail to be able to
+                // store them into a consumer group
+                let mut assignment = TopicPartitionList::new();
+                for (t, p) in topic_partition.iter() {
+                    let (low, high) = consumer.fetch_watermarks(t, *p, tmo)?;
+                    assignment.add_partition_offset(t, *p, Offset::Offset((high - count).max(low)))?;
+                }
+                trace!("Assign partitions {:?}", assignment);
+                consumer.assign(&assignment)?;
+            }
         }
         Ok(())
     }
+
+    // rdkafka reads the negative offsets as the logical ones (beginning, end, stored...)
+    fn validate_offset_config(config: &ConsumerOffsetConfiguration) -> Result<()> {
+        match config {
+            ConsumerOffsetConfiguration::LastRecords { count } if *count < 0 => Err(Error::Consumer {
+                message: format!("Invalid number of records {}", count),
+            }),
+            ConsumerOffsetConfiguration::PartitionOffsets { offsets } => {
+                match offsets.iter().find(|(_, offset)| **offset < 0) {
+                    Some((partition, offset)) => Err(Error::Consumer {
+                        message: format!("Invalid offset {} for partition {}", offset, partition),
+                    }),
+                    None => Ok(()),
+                }
+            }
+            _ => Ok(()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use rdkafka::{
+        message::{Header, OwnedHeaders, OwnedMessage},
+        Timestamp,
+    };
+
+    use crate::lib::{consumer::ConsumerOffsetConfiguration, types::RecordHeader};
+
+    use super::KafkaConsumer;
+
+    #[test]
+    fn test_map_headers() {
+        let headers = OwnedHeaders::new()
+            .insert(Header {
+                key: "text",
+                value: Some("value"),
+            })
+            .insert(Header {
+                key: "null",
+                value: None::<&[u8]>,
+            })
+            .insert(Header {
+                key: "binary",
+                value: Some(&[0xff, 0x00][..]),
+            });
+        let msg = OwnedMessage::new(None, None, "topic".into(), Timestamp::NotAvailable, 0, 0, Some(headers));
+
+        let record = KafkaConsumer::map_kafka_record(&msg);
+
+        assert_eq!(
+            record.headers,
+            vec![
+                RecordHeader {
+                    key: "text".into(),
+                    value: Some("value".into()),
+                    binary: false,
+                },
+                RecordHeader {
+                    key: "null".into(),
+                    value: None,
+                    binary: false,
+                },
+                RecordHeader {
+                    key: "binary".into(),
+                    value: Some("/wA=".into()),
+                    binary: true,
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn test_reject_negative_offsets() {
+        let validate = |config| KafkaConsumer::validate_offset_config(&config);
+        assert!(validate(ConsumerOffsetConfiguration::LastRecords { count: 0 }).is_ok());
+        assert!(validate(ConsumerOffsetConfiguration::LastRecords { count: -1 }).is_err());
+        assert!(validate(ConsumerOffsetConfiguration::PartitionOffsets {
+            offsets: HashMap::from([(0, 0), (1, 10)])
+        })
+        .is_ok());
+        assert!(validate(ConsumerOffsetConfiguration::PartitionOffsets {
+            offsets: HashMap::from([(0, 0), (1, -2)])
+        })
+        .is_err());
+    }
 }
@@ -1,4 +1,6 @@
 mod client;
+mod pipeline;
+mod stop_condition;
 pub mod types;
 pub use client::{Consumer, KafkaConsumer};
-pub use types::ConsumerOffsetConfiguration;
+pub use types::{ConsumerOffsetConfiguration, ConsumerStopConfiguration};
@@ -0,0 +1,490 @@
+use std::{
+    collections::{BTreeMap, HashMap},
+    sync::Arc,
+    time::Duration,
+};
+
+use futures::lock::Mutex;
+use log::{debug, error, warn};
+use tauri::async_runtime::JoinHandle;
+use tokio::{
+    sync::mpsc::{self, error::TrySendError},
+    time::{timeout, Instant},
+};
+
+use crate::lib::{
+    configuration::TopicSettings,
+    error::{Error, Result},
+    record_store::TopicStore,
+    types::{ParsedKafkaRecord, RawKafkaRecord, RecordFormat},
+};
+
+// records waiting to be decoded, the consumer pauses the partitions when the channel is full
+const CHANNEL_SIZE: usize = 2000;
+// the decoded records are stored in batches, when the batch is full or the interval is elapsed
+const INSERT_BATCH_SIZE: usize = 500;
+const INSERT_INTERVAL: Duration = Duration::from_millis(200);
+// the store limits are checked after storing a batch, at most once per interval
+const EVICTION_INTERVAL: Duration = Duration::from_secs(1);
+// decoded records waiting for a slower one, over the limit the missing records are skipped
+const MAX_PENDING_RECORDS: usize = 10_000;
+
+// Decode the consumed records in parallel and store them in the consumption order:
+// poll loop -> bounded channel -> decoding workers -> ordered batch writer
+pub(super) struct RecordPipeline {
+    sender: Option<mpsc::Sender<(u64, RawKafkaRecord)>>,
+    writer: Option<JoinHandle<Result<()>>>,
+    warnings: mpsc::UnboundedReceiver<String>,
+    next_sequence: u64,
+}
+
+impl RecordPipeline {
+    pub fn start(topic_store: Arc<TopicStore>, topic_settings: &TopicSettings) -> Self {
+        let (sender, receiver) = mpsc::channel(CHANNEL_SIZE);
+        let (decoded_sender, decoded_receiver) = mpsc::channel(CHANNEL_SIZE);
+        let (warning_sender, warnings) = mpsc::unbounded_channel();
+        let receiver = Arc::new(Mutex::new(receiver));
+        let topic_settings = Arc::new(topic_settings.clone());
+        let workers = std::thread::available_parallelism()
+            .map(|n| n.get())
+            .unwrap_or(2)
+            .clamp(2, 8);
+        debug!("Start {} decoding workers", workers);
+        for _ in 0..workers {
+            tauri::async_runtime::spawn(decode_records(
+                receiver.clone(),
+                decoded_sender.clone(),
+                topic_store.clone(),
+                topic_settings.clone(),
+            ));
+        }
+        RecordPipeline {
+            sender: Some(sender),
+            writer: Some(tauri::async_runtime::spawn(write_records(
+                topic_store,
+                decoded_receiver,
+                warning_sender,
+            ))),
+            warnings,
+            next_sequence: 0,
+        }
+    }
+
+    // issues that don't stop the pipeline, to report to the user
+    pub fn take_warnings(&mut self) -> Vec<String> {
+        let mut warnings = vec![];
+        while let Ok(warning) = self.warnings.try_recv() {
+            warnings.push(warning);
+        }
+        warnings
+    }
+
+    // false when the store is falling behind and the decoding channel is full
+    pub fn has_capacity(&self) -> bool {
+        self.sender.as_ref().map(|s| s.capacity() > 0).unwrap_or(false)
+    }
+
+    // true when half of the decoding channel is free again
+    pub fn is_drained(&self) -> bool {
+        self.sender
+            .as_ref()
+            .map(|s| s.capacity() >= CHANNEL_SIZE / 2)
+            .unwrap_or(true)
+    }
+
+    // wait for a free slot in the decoding channel if it's full.
+    // Fails with the error of the writer if it's stopped
+    pub async fn push(&mut self, record: RawKafkaRecord) -> Result<()> {
+        let closed = || Error::Consumer {
+            message: "The record pipeline is closed".into(),
+        };
+        let sender = self.sender.as_ref().ok_or_else(closed)?;
+        // the channel is closed when all the workers are stopped, after the writer
+        if sender.send((self.next_sequence, record)).await.is_err() {
+            self.sender.take();
+            return Err(self.wait_writer().await.err().unwrap_or_else(closed));
+        }
+        self.next_sequence += 1;
+        Ok(())
+    }
+
+    // wait for the records in the pipeline to be decoded and stored
+    pub async fn close(&mut self) -> Result<()> {
+        // the workers and then the writer stop when the channels are closed
+        self.sender.take();
+        self.wait_writer().await
+    }
+
+    async fn wait_writer(&mut self) -> Result<()> {
+        match self.writer.take() {
+            Some(writer) => writer.await.map_err(|err| Error::Consumer {
+                message: format!("The record writer failed: {}", err),
+            })?,
+            None => Ok(()),
+        }
+    }
+}
+
+async fn decode_records(
+    receiver: Arc<Mutex<mpsc::Receiver<(u64, RawKafkaRecord)>>>,
+    decoded_sender: mpsc::Sender<(u64, Option<ParsedKafkaRecord>)>,
+    topic_store: Arc<TopicStore>,
+    topic_settings: Arc<TopicSettings>,
+) {
+    loop {
+        // release the receiver before decoding, so the other workers can take the next records
+        let next = receiver.lock().await.recv().await;
+        let (sequence, record) = match next {
+            Some(next) => next,
+            None => break,
+        };
+        let guard = SequenceGuard {
+            sequence,
+            sender: decoded_sender.clone(),
+            is_sent: false,
+        };
+        // decode in a separate task, so a panic fails the record and not the worker
+        let decoding = tauri::async_runtime::spawn({
+            let (topic_store, topic_settings, record) = (topic_store.clone(), topic_settings.clone(), record.clone());
+            async move { topic_store.parse_record(&record, &topic_settings).await }
+        });
+        let decoded = match decoding.await {
+            Ok(Ok(decoded)) => decoded,
+            Ok(Err(err)) => failed_record(record, &err.to_string()),
+            Err(err) => failed_record(record, &format!("The decoder failed: {}", err)),
+        };
+        if !guard.send(decoded).await {
+            break;
+        }
+    }
+}
+
+// store the raw record with the error, so it's not lost
+fn failed_record(record: RawKafkaRecord, err: &str) -> ParsedKafkaRecord {
+    error!(
+        "Unable to decode the record at offset {} of partition {}: {}",
+        record.offset, record.partition, err
+    );
+    let to_string = |raw: &Option<Vec<u8>>| raw.as_ref().map(|v| String::from_utf8_lossy(v).to_string());
+    ParsedKafkaRecord {
+        key: to_string(&record.key),
+        key_format: record.key.as_ref().map(|_| RecordFormat::String),
+        payload: to_string(&record.payload),
+        payload_format: record.payload.as_ref().map(|_| RecordFormat::String),
+        validation_errors: None,
+        parse_error: Some(format!("Unable to parse the record: {}", err)),
+        key_schema_id: None,
+        payload_schema_id: None,
+        raw_key: record.key,
+        raw_payload: record.payload,
+        topic: record.topic,
+        timestamp: record.timestamp,
+        partition: record.partition,
+        offset: record.offset,
+        headers: record.headers,
+        projections: HashMap::new(),
+    }
+}
+
+// the writer waits for every sequence to keep the order. If the decoding panics or the worker
+// is cancelled, the guard sends the sequence without a record so the next ones are not blocked
+struct SequenceGuard {
+    sequence: u64,
+    sender: mpsc::Sender<(u64, Option<ParsedKafkaRecord>)>,
+    is_sent: bool,
+}
+
+impl SequenceGuard {
+    // false when the writer is stopped
+    async fn send(mut self, record: ParsedKafkaRecord) -> bool {
+        // a cancelled send doesn't send the record, so the guard still has to release the sequence
+        let res = self.sender.send((self.sequence, Some(record))).await;
+        self.is_sent = true;
+        res.is_ok()
+    }
+}
+
+impl Drop for SequenceGuard {
+    fn drop(&mut self) {
+        if self.is_sent {
+            return;
+        }
+        // drop can't wait for a free slot in the channel
+        if let Err(TrySendError::Full(item)) = self.sender.try_send((self.sequence, None)) {
+            let sender = self.sender.clone();
+            tauri::async_runtime::spawn(async move {
+                let _ = sender.send(item).await;
+            });
+        }
+    }
+}
+
+// the error stops the writer, then the workers and the consumer loop
+async fn write_records(
+    topic_store: Arc<TopicStore>,
+    mut decoded_receiver: mpsc::Receiver<(u64, Option<ParsedKafkaRecord>)>,
+    warnings: mpsc::UnboundedSender<String>,
+) -> Result<()> {
+    let mut reorder_buffer = ReorderBuffer::new(MAX_PENDING_RECORDS);
+    let mut batch = Vec::with_capacity(INSERT_BATCH_SIZE);
+    let mut last_insert = Instant::now();
+    let mut last_eviction = Instant::now();
+    loop {
+        let is_closed = match timeout(
+            INSERT_INTERVAL.saturating_sub(last_insert.elapsed()),
+            decoded_receiver.recv(),
+        )
+        .await
+        {
+            Ok(Some((sequence, record))) => {
+                batch.extend(reorder_buffer.push(sequence, record));
+                let skipped = reorder_buffer.take_skipped();
+                if skipped > 0 {
+                    let warning = format!(
+                        "{} records are taking too long to decode, they will be stored out of order",
+                        skipped
+                    );
+                    warn!("{}", warning);
+                    let _ = warnings.send(warning);
+                }
+                false
+            }
+            Ok(None) => true,
+            // the insert interval is elapsed
+            Err(_) => false,
+        };
+        if is_closed || batch.len() >= INSERT_BATCH_SIZE || last_insert.elapsed() >= INSERT_INTERVAL {
+            last_insert = Instant::now();
+            if !batch.is_empty() {
+                if let Err(err) = topic_store.store_records(&batch).await {
+                    error!("Unable to store the consumed records: {}", err);
+                    return Err(err);
+                }
+                batch.clear();
+                if is_closed || last_eviction.elapsed() >= EVICTION_INTERVAL {
+                    last_eviction = Instant::now();
+                    match topic_store.evict_records().await {
+                        Ok(0) => (),
+                        Ok(count) => debug!("Evicted {} records", count),
+                        Err(err) => error!("Unable to evict the records exceeding the store limits: {}", err),
+                    }
+                }
+            }
+        }
+        if is_closed {
+            return Ok(());
+        }
+    }
+}
+
+// release the items in the sequence order, the missing items (None) are skipped
+struct ReorderBuffer<T> {
+    next_sequence: u64,
+    pending: BTreeMap<u64, Option<T>>,
+    max_pending: usize,
+    // missing sequences skipped since the last take_skipped
+    skipped: u64,
+}
+
+impl<T> ReorderBuffer<T> {
+    fn new(max_pending: usize) -> Self {
+        ReorderBuffer {
+            next_sequence: 0,
+            pending: BTreeMap::new(),
+            max_pending,
+            skipped: 0,
+        }
+    }
+
+    fn take_skipped(&mut self) -> u64 {
+        std::mem::take(&mut self.skipped)
+    }
+
+    fn push(&mut self, sequence: u64, item: Option<T>) -> Vec<T> {
+        // a skipped item is released as soon as it arrives
+        if sequence < self.next_sequence {
+            return item.into_iter().collect();
+        }
+        self.pending.insert(sequence, item);
+        if self.pending.len() > self.max_pending {
+            if let Some(&first) = self.pending.keys().next() {
+                self.skipped += first - self.next_sequence;
+                self.next_sequence = first;
+            }
+        }
+        let mut ready = vec![];
+        while let Some(item) = self.pending.remove(&self.next_sequence) {
+            self.next_sequence += 1;
+            ready.extend(item);
+        }
+        ready
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{sync::Arc, time::Duration};
+
+    use async_trait::async_trait;
+    use tokio::{
+        sync::Semaphore,
+        time::{sleep, timeout},
+    };
+
+    use crate::lib::{
+        configuration::TopicSettings,
+        error::{Error, Result},
+        parser::{Parser, RecordParser},
+        record_store::{AppStore, TopicStore},
+        types::{ParsedKafkaRecord, RawKafkaRecord},
+    };
+
+    use super::{RecordPipeline, ReorderBuffer, CHANNEL_SIZE};
+
+    // waits for the gate to open, then the records with a round offset take longer to decode.
+    // It panics more times than the number of workers
+    struct SlowParser {
+        gate: Arc<Semaphore>,
+        parser: RecordParser,
+    }
+
+    #[async_trait]
+    impl Parser for SlowParser {
+        async fn parse_record(&self, record: &RawKafkaRecord, settings: &TopicSettings) -> Result<ParsedKafkaRecord> {
+            drop(self.gate.acquire().await.unwrap());
+            if record.offset % 100 == 0 {
+                sleep(Duration::from_millis(20)).await;
+            }
+            match record.offset {
+                1 => Err(Error::Consumer {
+                    message: "invalid record".into(),
+                }),
+                offset if offset % 50 == 2 => panic!("decoder failure"),
+                _ => self.parser.parse_record(record, settings).await,
+            }
+        }
+    }
+
+    #[tokio::test]
+    async fn test_store_records_in_order() {
+        // arrange
+        let gate = Arc::new(Semaphore::new(0));
+        let parser = SlowParser {
+            gate: gate.clone(),
+            parser: RecordParser::new(None),
+        };
+        let topic_store = Arc::new(
+            TopicStore::from_app_store(Arc::new(AppStore::new()), Arc::new(parser), "cluster_id", "topic_name").await,
+        );
+        let mut pipeline = RecordPipeline::start(topic_store.clone(), &TopicSettings::default());
+        // act
+        // the decoding is blocked, so the channel fills up and the consumer has to pause
+        let mut count = 0;
+        while pipeline.has_capacity() {
+            pipeline.push(get_raw_record(count)).await.unwrap();
+            count += 1;
+        }
+        assert!(count >= CHANNEL_SIZE as i64);
+        assert!(!pipeline.is_drained());
+        gate.add_permits(1);
+        // and resumes when half of the channel is free again
+        timeout(Duration::from_secs(10), async {
+            while !pipeline.is_drained() {
+                sleep(Duration::from_millis(10)).await;
+            }
+        })
+        .await
+        .unwrap();
+        pipeline.close().await.unwrap();
+        // assert
+        let records = topic_store
+            .get_records(
+                Some("SELECT partition, offset, timestamp, key, payload, parse_error FROM {:topic} ORDER BY rowid"),
+                0,
+                -1,
+            )
+            .await
+            .unwrap();
+        // the records that failed to decode are stored with the error
+        let offsets: Vec<_> = records.iter().map(|r| r.offset).collect();
+        assert_eq!(offsets, (0..count).collect::<Vec<_>>());
+        assert_eq!(records[1].payload, Some("payload-1".into()));
+        assert!(records[1].parse_error.as_ref().unwrap().contains("invalid record"));
+        assert!(records[2].parse_error.as_ref().unwrap().contains("The decoder failed"));
+        assert_eq!(records[3].parse_error, None);
+        assert!(records[count as usize - 1].parse_error.is_none());
+    }
+
+    #[tokio::test]
+    async fn test_report_the_store_error() {
+        // arrange
+        let (topic_store, app_store) = get_topic_store().await;
+        // the writer fails to store the records without the topic table
+        app_store.delete_session("cluster_id", "topic_name").await.unwrap();
+        let mut pipeline = RecordPipeline::start(topic_store.clone(), &TopicSettings::default());
+        // act
+        let push_error = timeout(Duration::from_secs(10), async {
+            let mut offset = 0;
+            loop {
+                if let Err(err) = pipeline.push(get_raw_record(offset)).await {
+                    return err;
+                }
+                offset += 1;
+            }
+        })
+        .await
+        .unwrap();
+        let mut pipeline = RecordPipeline::start(topic_store, &TopicSettings::default());
+        pipeline.push(get_raw_record(0)).await.unwrap();
+        let close_error = pipeline.close().await.unwrap_err();
+        // assert
+        assert!(push_error.to_string().contains("no such table"));
+        assert!(close_error.to_string().contains("no such table"));
+    }
+
+    #[test]
+    fn test_release_items_in_order() {
+        let mut buffer = ReorderBuffer::new(10);
+        assert_eq!(buffer.push(1, Some("b")), Vec::<&str>::new());
+        assert_eq!(buffer.push(3, Some("d")), Vec::<&str>::new());
+        assert_eq!(buffer.push(0, Some("a")), vec!["a", "b"]);
+        // a record that failed to decode doesn't block the next ones
+        assert_eq!(buffer.push(2, None), vec!["d"]);
+        assert_eq!(buffer.push(4, Some("e")), vec!["e"]);
+        assert!(buffer.pending.is_empty());
+    }
+
+    #[test]
+    fn test_skip_missing_items_over_the_limit() {
+        let mut buffer = ReorderBuffer::new(2);
+        assert_eq!(buffer.push(1, Some("b")), Vec::<&str>::new());
+        assert_eq!(buffer.push(2, Some("c")), Vec::<&str>::new());
+        // the missing item is skipped and released when it arrives
+        assert_eq!(buffer.push(3, Some("d")), vec!["b", "c", "d"]);
+        assert_eq!(buffer.take_skipped(), 1);
+        assert_eq!(buffer.push(0, Some("a")), vec!["a"]);
+        assert_eq!(buffer.take_skipped(), 0);
+        assert_eq!(buffer.push(4, Some("e")), vec!["e"]);
+        assert!(buffer.pending.is_empty());
+    }
+
+    async fn get_topic_store() -> (Arc<TopicStore>, Arc<AppStore>) {
+        let app_store = Arc::new(AppStore::new());
+        let parser: RecordParser = RecordParser::new(None);
+        let topic_store =
+            TopicStore::from_app_store(app_store.clone(), Arc::new(parser), "cluster_id", "topic_name").await;
+        (Arc::new(topic_store), app_store)
+    }
+
+    fn get_raw_record(offset: i64) -> RawKafkaRecord {
+        RawKafkaRecord {
+            topic: "topic_name".into(),
+            key: None,
+            payload: Some(format!("payload-{}", offset).into_bytes()),
+            timestamp: Some(offset),
+            partition: 0,
+            offset,
+            headers: vec![],
+        }
+    }
+}
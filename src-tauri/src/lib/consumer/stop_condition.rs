@@ -0,0 +1,166 @@
+use std::collections::{HashMap, HashSet};
+
+// Track the consumed records to find out when the consumer
+// reached the configured boundary in all the assigned partitions
+pub(super) struct StopCondition {
+    bounded: bool,
+    max_records: Option<usize>,
+    stop_timestamp: Option<i64>,
+    // offset after the last record to consume in each partition
+    end_offsets: HashMap<i32, i64>,
+    pending_partitions: HashSet<i32>,
+    consumed_records: usize,
+}
+
+impl StopCondition {
+    pub fn new(
+        start_offsets: HashMap<i32, i64>,
+        end_offsets: HashMap<i32, i64>,
+        stop_timestamp: Option<i64>,
+        max_records: Option<usize>,
+    ) -> Self {
+        let pending_partitions = if max_records == Some(0) {
+            HashSet::new()
+        } else {
+            // skip the partitions without records to consume
+            start_offsets
+                .iter()
+                .filter(|(partition, start)| end_offsets.get(partition).map(|end| end > start).unwrap_or(true))
+                .map(|(partition, _)| *partition)
+                .collect()
+        };
+        StopCondition {
+            bounded: max_records.is_some() || stop_timestamp.is_some() || !end_offsets.is_empty(),
+            max_records,
+            stop_timestamp,
+            end_offsets,
+            pending_partitions,
+            consumed_records: 0,
+        }
+    }
+
+    // returns true if the record is within the boundary and should be stored
+    pub fn check(&mut self, partition: i32, offset: i64, timestamp: Option<i64>) -> bool {
+        if !self.bounded {
+            return true;
+        }
+        if !self.pending_partitions.contains(&partition) {
+            return false;
+        }
+        if let (Some(stop_timestamp), Some(timestamp)) = (self.stop_timestamp, timestamp) {
+            if timestamp > stop_timestamp {
+                self.pending_partitions.remove(&partition);
+                return false;
+            }
+        }
+        if let Some(end_offset) = self.end_offsets.get(&partition) {
+            if offset >= *end_offset {
+                self.pending_partitions.remove(&partition);
+                return false;
+            } else if offset + 1 == *end_offset {
+                self.pending_partitions.remove(&partition);
+            }
+        }
+        self.consumed_records += 1;
+        if Some(self.consumed_records) == self.max_records {
+            self.pending_partitions.clear();
+        }
+        true
+    }
+
+    // the position is the offset of the next record to fetch. It can reach the end offset
+    // without returning the record before it, when the last offsets of the partition
+    // are transaction markers or records removed by the compaction
+    pub fn check_position(&mut self, partition: i32, position: i64) {
+        if matches!(self.end_offsets.get(&partition), Some(end_offset) if position >= *end_offset) {
+            self.pending_partitions.remove(&partition);
+        }
+    }
+
+    pub fn is_completed(&self) -> bool {
+        self.bounded && self.pending_partitions.is_empty()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use super::StopCondition;
+
+    #[test]
+    fn test_unbounded_never_completes() {
+        let mut sut = StopCondition::new(HashMap::from([(0, 0)]), HashMap::new(), None, None);
+        for offset in 0..100 {
+            assert!(sut.check(0, offset, Some(offset)));
+        }
+        assert!(!sut.is_completed());
+    }
+
+    #[test]
+    fn test_stop_after_n_records() {
+        let mut sut = StopCondition::new(HashMap::from([(0, 0), (1, 0)]), HashMap::new(), None, Some(3));
+        assert!(sut.check(0, 0, None));
+        assert!(sut.check(1, 0, None));
+        assert!(!sut.is_completed());
+        assert!(sut.check(0, 1, None));
+        assert!(sut.is_completed());
+        assert!(!sut.check(1, 1, None));
+    }
+
+    #[test]
+    fn test_stop_at_end_offsets() {
+        // partition 2 is empty
+        let mut sut = StopCondition::new(
+            HashMap::from([(0, 0), (1, 5), (2, 3)]),
+            HashMap::from([(0, 2), (1, 6), (2, 3)]),
+            None,
+            None,
+        );
+        assert!(sut.check(0, 0, None));
+        assert!(sut.check(1, 5, None));
+        assert!(!sut.is_completed());
+        assert!(sut.check(0, 1, None));
+        assert!(sut.is_completed());
+        assert!(!sut.check(0, 2, None));
+    }
+
+    #[test]
+    fn test_stop_at_end_position() {
+        // the offsets 3 and 4 of partition 0 are transaction markers
+        let mut sut = StopCondition::new(
+            HashMap::from([(0, 0), (1, 0)]),
+            HashMap::from([(0, 5), (1, 1)]),
+            None,
+            None,
+        );
+        for offset in 0..3 {
+            assert!(sut.check(0, offset, None));
+        }
+        sut.check_position(0, 3);
+        assert!(sut.check(1, 0, None));
+        assert!(!sut.is_completed());
+        sut.check_position(0, 5);
+        assert!(sut.is_completed());
+    }
+
+    #[test]
+    fn test_stop_at_timestamp() {
+        let mut sut = StopCondition::new(HashMap::from([(0, 0), (1, 0)]), HashMap::new(), Some(100), None);
+        assert!(sut.check(0, 0, Some(50)));
+        assert!(!sut.check(0, 1, Some(101)));
+        assert!(!sut.check(0, 2, Some(60)));
+        assert!(!sut.is_completed());
+        assert!(sut.check(1, 0, Some(100)));
+        assert!(!sut.check(1, 1, Some(200)));
+        assert!(sut.is_completed());
+    }
+
+    #[test]
+    fn test_nothing_to_consume() {
+        let sut = StopCondition::new(HashMap::from([(0, 10)]), HashMap::from([(0, 10)]), None, None);
+        assert!(sut.is_completed());
+        let sut = StopCondition::new(HashMap::from([(0, 0)]), HashMap::new(), None, Some(0));
+        assert!(sut.is_completed());
+    }
+}
@@ -1,5 +1,9 @@
+use std::collections::HashMap;
+
 use serde::{Deserialize, Serialize};
 
+use crate::lib::configuration::StoreLimits;
+
 #[derive(Serialize, Deserialize, Debug, Clone)]
 pub enum ConsumerOffsetConfiguration {
     Beginning,
@@ -8,12 +12,60 @@ pub enum ConsumerOffsetConfiguration {
         start_timestamp: i64,        //time in ms
         stop_timestamp: Option<i64>, //time in ms
     },
+    // start from the given offset, only in the listed partitions
+    PartitionOffsets {
+        offsets: HashMap<i32, i64>, //partition -> offset
+    },
+    // start from the last N records of each partition
+    LastRecords {
+        count: i64,
+    },
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+pub enum ConsumerStopConfiguration {
+    #[default]
+    Never,
+    RecordCount(usize),
+    // stop at the last offset available when the consumer starts
+    CurrentEnd,
 }
 
 #[derive(Serialize, Deserialize, Clone)]
 pub struct ConsumerState {
     #[serde(rename = "isRunning")]
     pub is_running: bool,
+    #[serde(rename = "isFinished")]
+    pub is_finished: bool,
+    #[serde(rename = "recordCount")]
+    pub record_count: usize,
+    // records removed from the store to respect the limits
+    #[serde(rename = "evictedCount")]
+    pub evicted_count: usize,
+    #[serde(rename = "topicLimits")]
+    pub topic_limits: StoreLimits,
+    #[serde(rename = "globalLimits")]
+    pub global_limits: StoreLimits,
+}
+
+#[derive(Serialize, Clone, Debug)]
+pub enum ConsumerEvent {
+    Started,
+    Stopped,
+    Error { message: String },
+    Progress(ConsumerProgress),
+}
+
+#[derive(Serialize, Clone, Debug)]
+pub struct ConsumerProgress {
     #[serde(rename = "recordCount")]
     pub record_count: usize,
+    pub partitions: Vec<PartitionProgress>,
+}
+
+#[derive(Serialize, Clone, Debug)]
+pub struct PartitionProgress {
+    pub partition: i32,
+    pub offset: i64, //last consumed offset
+    pub lag: i64,    //-1 if unknown
 }
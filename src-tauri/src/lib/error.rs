@@ -3,15 +3,35 @@ use rdkafka::error::KafkaError;
 #[derive(Debug)]
 pub enum Error {
     AvroParse { message: String },
+    ProtobufParse { message: String },
+    JsonSchemaParse { message: String },
     IO { message: String },
     JSONSerde { message: String },
     Consumer { message: String },
     Kafka { message: String },
     SqlError { message: String },
+    // position is the char offset of the error in the query template, if known
+    InvalidQuery { message: String, position: Option<usize> },
 }
 
 pub(super) type Result<T> = core::result::Result<T, Error>;
 
+impl std::fmt::Display for Error {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Error::AvroParse { message }
+            | Error::ProtobufParse { message }
+            | Error::JsonSchemaParse { message }
+            | Error::IO { message }
+            | Error::JSONSerde { message }
+            | Error::Consumer { message }
+            | Error::Kafka { message }
+            | Error::SqlError { message }
+            | Error::InvalidQuery { message, .. } => write!(f, "{}", message),
+        }
+    }
+}
+
 impl From<std::io::Error> for Error {
     fn from(error: std::io::Error) -> Self {
         Error::IO {
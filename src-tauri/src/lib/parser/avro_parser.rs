@@ -1,20 +1,54 @@
-use std::{collections::HashMap, io::Cursor, sync::Arc};
+use std::{
+    collections::HashMap,
+    io::Cursor,
+    sync::Arc,
+    time::{Duration, Instant},
+};
 
 use apache_avro::{from_avro_datum, schema::Name, types::Value as AvroValue, Schema};
-use num_bigint::BigInt;
-use rust_decimal::Decimal;
+use futures::lock::Mutex;
+use num_bigint::{BigInt, Sign};
+use serde::{Deserialize, Serialize};
 use serde_json::{json, Map, Value as JsonValue};
+use time::OffsetDateTime;
 
 use crate::lib::{
     error::{Error, Result},
     schema_registry::{CachedSchemaRegistry, SchemaRegistryClient},
 };
 
+// the latest version of a reader schema is retrieved again after this interval
+const LATEST_READER_SCHEMA_TTL: Duration = Duration::from_secs(60);
+
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq, Eq)]
+pub enum AvroEncoding {
+    // unions are flattened, bytes are arrays of integers and decimals are strings
+    #[default]
+    Friendly,
+    // friendly encoding with the ISO-8601 string of the date, time and timestamp fields
+    // in a sibling field named <field>_readable. The numeric values are kept for the queries
+    Readable,
+    // standard avro json encoding, that can be produced back to kafka
+    AvroJson,
+}
+
+// subject version used as reader schema, to resolve all the records to the same shape
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq, Hash)]
+pub struct AvroReaderSchema {
+    pub subject: String,
+    // latest version if not specified
+    pub version: Option<i32>,
+}
+
+// reader schemas with the time they were retrieved
+type ReaderSchemaCache = HashMap<AvroReaderSchema, (Arc<Schema>, Instant)>;
+
 pub struct AvroParser<C = CachedSchemaRegistry>
 where
     C: SchemaRegistryClient + Send + Sync,
 {
     schema_registry_client: Arc<C>,
+    reader_schema_cache: Arc<Mutex<ReaderSchemaCache>>,
 }
 
 impl<C> AvroParser<C>
@@ -22,11 +56,19 @@ where
     C: SchemaRegistryClient + Send + Sync,
 {
     pub fn new(schema_registry_client: Arc<C>) -> Self {
-        AvroParser { schema_registry_client }
+        AvroParser {
+            schema_registry_client,
+            reader_schema_cache: Arc::new(Mutex::new(HashMap::new())),
+        }
     }
 
-    pub async fn parse_payload(&self, raw: &[u8]) -> Result<String> {
-        if raw.len() <= 5 || raw[0] != 0x00 {
+    pub async fn parse_payload(
+        &self,
+        raw: &[u8],
+        encoding: &AvroEncoding,
+        reader_schema: Option<&AvroReaderSchema>,
+    ) -> Result<String> {
+        if raw.len() < 5 || raw[0] != 0x00 {
             return Err(Error::AvroParse {
                 message: "Supported avro messages should start with 0x00 follow by the schema id (4 bytes)".into(),
             });
@@ -45,16 +87,56 @@ where
                     err.to_string()
                 ),
             })?;
+        let reader_schema = match reader_schema {
+            Some(reader_schema) => Some(self.get_reader_schema(reader_schema).await?),
+            None => None,
+        };
         let mut data = Cursor::new(&raw[5..]);
-        let record = from_avro_datum(&schema, &mut data, None).map_err(|err| Error::AvroParse {
+        let record = from_avro_datum(&schema, &mut data, reader_schema.as_deref()).map_err(|err| Error::AvroParse {
             message: format!("{}\n{}", "Unable to parse the avro record", err),
         })?;
-        let json = map(&record, &schema, &mut HashMap::new())?;
+        // the record has been resolved to the reader schema, if any
+        let json = map(
+            &record,
+            reader_schema.as_deref().unwrap_or(&schema),
+            encoding,
+            &mut HashMap::new(),
+        )?;
         let res = serde_json::to_string(&json).map_err(|err| Error::AvroParse {
             message: format!("{}\n{}", "Unable to map the avro record to json", err),
         })?; // todo: maybe pretty_print
         Ok(res)
     }
+
+    // the explicit versions are cached forever, the latest version
+    // is retrieved again after LATEST_READER_SCHEMA_TTL to pick up the new versions
+    async fn get_reader_schema(&self, reader_schema: &AvroReaderSchema) -> Result<Arc<Schema>> {
+        let mut cache = self.reader_schema_cache.lock().await;
+        if let Some((cached, retrieved_at)) = cache.get(reader_schema) {
+            if reader_schema.version.is_some() || retrieved_at.elapsed() < LATEST_READER_SCHEMA_TTL {
+                return Ok(cached.clone());
+            }
+        }
+        let map_err = |err: String| Error::AvroParse {
+            message: format!(
+                "Unable to retrieve the reader schema for {} from schema registry\n{}",
+                reader_schema.subject, err
+            ),
+        };
+        let version = self
+            .schema_registry_client
+            .get_schema_version(&reader_schema.subject, reader_schema.version)
+            .await
+            .map_err(|err| map_err(err.to_string()))?;
+        let schema = Arc::new(
+            self.schema_registry_client
+                .get_schema_by_id(version.id)
+                .await
+                .map_err(|err| map_err(err.to_string()))?,
+        );
+        cache.insert(reader_schema.clone(), (schema.clone(), Instant::now()));
+        Ok(schema)
+    }
 }
 
 fn get_schema_id(raw: &[u8]) -> Result<i32> {
@@ -67,8 +149,11 @@ fn get_schema_id(raw: &[u8]) -> Result<i32> {
 fn map<'a>(
     value: &AvroValue,
     schema: &'a Schema,
+    encoding: &AvroEncoding,
     ref_cache: &mut HashMap<&'a Name, &'a Schema>, //cache to resolve avro references
 ) -> Result<JsonValue> {
+    let avro_json = *encoding == AvroEncoding::AvroJson;
+    let readable = *encoding == AvroEncoding::Readable;
     match (value, schema) {
         (AvroValue::Null, Schema::Null) => Ok(JsonValue::Null),
         (AvroValue::Boolean(v), Schema::Boolean) => Ok(json!(*v)),
@@ -80,7 +165,7 @@ fn map<'a>(
         (AvroValue::Array(v), Schema::Array(s)) => {
             let mut json_vec = Vec::new();
             for v in v.iter() {
-                json_vec.push(map(v, s, ref_cache)?);
+                json_vec.push(map(v, s, encoding, ref_cache)?);
             }
             Ok(JsonValue::Array(json_vec))
         }
@@ -88,7 +173,7 @@ fn map<'a>(
             //todo: DRY
             let mut json_map = Map::new();
             for (k, v) in vec.iter() {
-                json_map.insert(k.clone(), map(v, s, ref_cache)?);
+                json_map.insert(k.clone(), map(v, s, encoding, ref_cache)?);
             }
             Ok(JsonValue::Object(json_map))
         }
@@ -101,8 +186,18 @@ fn map<'a>(
             ref_cache.insert(name, schema);
             let mut json_map = Map::new();
             for (k, v) in vec.iter() {
-                let field_index = lookup.get(k).unwrap_or_else(|| panic!("Missing field {}", k));
-                json_map.insert(k.clone(), map(v, &fields.get(*field_index).unwrap().schema, ref_cache)?);
+                let field = lookup.get(k).and_then(|i| fields.get(*i)).ok_or(Error::AvroParse {
+                    message: format!("Missing field {} in the schema of {}", k, name.name),
+                })?;
+                json_map.insert(k.clone(), map(v, &field.schema, encoding, ref_cache)?);
+                if readable {
+                    if let Some(readable_value) = format_logical_value(v)? {
+                        // a field of the record with the same name takes precedence
+                        json_map
+                            .entry(format!("{}_readable", k))
+                            .or_insert_with(|| json!(readable_value));
+                    }
+                }
             }
             Ok(JsonValue::Object(json_map))
         }
@@ -112,8 +207,8 @@ fn map<'a>(
         (AvroValue::TimestampMillis(v), Schema::TimestampMillis) => Ok(json!(*v)),
         (AvroValue::TimestampMicros(v), Schema::TimestampMicros) => Ok(json!(*v)),
         (AvroValue::Uuid(v), Schema::Uuid) => Ok(json!(*v)),
-        //todo: WIP
-        (AvroValue::Bytes(v), Schema::Bytes) => Ok(json!(*v)), //todo: this should be like "\u00FF"
+        (AvroValue::Bytes(v), Schema::Bytes) if avro_json => Ok(json!(to_iso_8859_1(v))),
+        (AvroValue::Bytes(v), Schema::Bytes) => Ok(json!(*v)),
         (
             AvroValue::Decimal(v),
             Schema::Decimal {
@@ -122,64 +217,207 @@ fn map<'a>(
                 inner: _,
             },
         ) => {
-            let arr = <Vec<u8>>::try_from(v).expect("Invalid decimal received");
+            let arr = <Vec<u8>>::try_from(v).map_err(|err| Error::AvroParse {
+                message: format!("{}\n{}", "Invalid decimal received", err),
+            })?;
+            if avro_json {
+                return Ok(json!(to_iso_8859_1(&arr)));
+            }
             let value = BigInt::from_signed_bytes_be(&arr);
-            let decimal = Decimal::new(
-                i64::try_from(value).expect("Unable to cast to i64"),
-                scale.to_owned() as u32,
-            );
-            Ok(json!(decimal))
+            Ok(json!(format_decimal(&value, *scale)))
         }
         (AvroValue::Duration(v), Schema::Duration) => {
-            //todo: check avro json representation
-            Ok(json!(format!(
-                "{:?} months {:?} days {:?} millis",
-                v.months(),
-                v.days(),
-                v.millis()
-            )))
+            let (months, days, millis) = (u32::from(v.months()), u32::from(v.days()), u32::from(v.millis()));
+            if avro_json {
+                // fixed of 12 bytes: months, days and millis as little-endian u32
+                let bytes: Vec<u8> = [months, days, millis].iter().flat_map(|v| v.to_le_bytes()).collect();
+                return Ok(json!(to_iso_8859_1(&bytes)));
+            }
+            Ok(json!(format!("{} months {} days {} millis", months, days, millis)))
         }
-        //todo: use avro-json format
         (AvroValue::Union(i, v), Schema::Union(s)) => {
-            let schema = s.variants().get(*i as usize).expect("Missing schema in the union");
+            let schema = s.variants().get(*i as usize).ok_or(Error::AvroParse {
+                message: format!("Missing schema for the union variant {}", i),
+            })?;
+            // the named types defined in the union can be referenced by the next fields,
+            // even if the value is another variant
             for s in s.variants() {
-                if let Schema::Record { name, .. } = s {
+                if let Schema::Record { name, .. } | Schema::Enum { name, .. } | Schema::Fixed { name, .. } = s {
                     ref_cache.insert(name, s);
                 }
             }
-            map(&**v, schema, ref_cache)
+            // the branch would be named after the fixed of the duration, which is not available
+            if avro_json && matches!(schema, Schema::Duration) {
+                return Err(Error::AvroParse {
+                    message: "Unable to encode a duration in a union with the avro json encoding".into(),
+                });
+            }
+            let value = map(&**v, schema, encoding, ref_cache)?;
+            // avro json wraps non null union values in an object with the type name as key
+            if avro_json && !matches!(schema, Schema::Null) {
+                let mut json_map = Map::new();
+                json_map.insert(get_type_name(schema), value);
+                Ok(JsonValue::Object(json_map))
+            } else {
+                Ok(value)
+            }
         }
         (AvroValue::Enum(_, v), Schema::Enum { name, .. }) => {
             ref_cache.insert(name, schema);
             Ok(json!(*v))
         }
-        //todo: check representation in avro-json
         (AvroValue::Fixed(_, v), Schema::Fixed { name, .. }) => {
             ref_cache.insert(name, schema);
-            Ok(json!(*v))
+            if avro_json {
+                Ok(json!(to_iso_8859_1(v)))
+            } else {
+                Ok(json!(*v))
+            }
         }
         (value, Schema::Ref { name }) => {
-            let schema = ref_cache
-                .get(name)
-                .unwrap_or_else(|| panic!("Missing Avro schema reference {:?}", name));
-            map(value, schema, ref_cache)
+            let schema = ref_cache.get(name).ok_or(Error::AvroParse {
+                message: format!("Missing Avro schema reference {:?}", name),
+            })?;
+            map(value, schema, encoding, ref_cache)
+        }
+        (v, s) => Err(Error::AvroParse {
+            message: format!("Unexpected value/schema tuple. Value: {:?} Schema: {:?}", v, s),
+        }),
+    }
+}
+
+// bytes and fixed are strings in which each char is a byte (ISO-8859-1)
+fn to_iso_8859_1(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| *b as char).collect()
+}
+
+fn get_type_name(schema: &Schema) -> String {
+    match schema {
+        Schema::Record { name, .. } | Schema::Enum { name, .. } | Schema::Fixed { name, .. } | Schema::Ref { name } => {
+            match &name.namespace {
+                Some(namespace) => format!("{}.{}", namespace, name.name),
+                None => name.name.clone(),
+            }
         }
-        (_, s) => panic!("Unexpected value/schema tuple. Schema: {:?}", s),
+        Schema::Null => "null".into(),
+        Schema::Boolean => "boolean".into(),
+        Schema::Int | Schema::Date | Schema::TimeMillis => "int".into(),
+        Schema::Long | Schema::TimeMicros | Schema::TimestampMillis | Schema::TimestampMicros => "long".into(),
+        Schema::Float => "float".into(),
+        Schema::Double => "double".into(),
+        Schema::Bytes => "bytes".into(),
+        Schema::String | Schema::Uuid => "string".into(),
+        Schema::Array(_) => "array".into(),
+        Schema::Map(_) => "map".into(),
+        Schema::Decimal { inner, .. } => get_type_name(inner),
+        _ => "union".into(),
+    }
+}
+
+// ISO-8601 string of the dates, times and timestamps, also in a nullable union
+fn format_logical_value(value: &AvroValue) -> Result<Option<String>> {
+    match value {
+        AvroValue::Date(v) => format_date(*v).map(Some),
+        AvroValue::TimeMillis(v) => format_time(*v as i64 * 1000, false).map(Some),
+        AvroValue::TimeMicros(v) => format_time(*v, true).map(Some),
+        AvroValue::TimestampMillis(v) => format_timestamp(*v as i128 * 1_000_000, false).map(Some),
+        AvroValue::TimestampMicros(v) => format_timestamp(*v as i128 * 1000, true).map(Some),
+        AvroValue::Union(_, v) => format_logical_value(v),
+        _ => Ok(None),
+    }
+}
+
+// dates, times and timestamps are rendered as ISO-8601 strings in UTC
+fn format_date(days: i32) -> Result<String> {
+    let date = to_datetime(days as i128 * 86_400_000_000_000)?;
+    Ok(format!(
+        "{:04}-{:02}-{:02}",
+        date.year(),
+        date.month() as u8,
+        date.day()
+    ))
+}
+
+fn format_timestamp(nanos: i128, micros_precision: bool) -> Result<String> {
+    let datetime = to_datetime(nanos)?;
+    let fraction = if micros_precision {
+        format!("{:06}", datetime.microsecond())
+    } else {
+        format!("{:03}", datetime.millisecond())
+    };
+    Ok(format!(
+        "{:04}-{:02}-{:02}T{:02}:{:02}:{:02}.{}Z",
+        datetime.year(),
+        datetime.month() as u8,
+        datetime.day(),
+        datetime.hour(),
+        datetime.minute(),
+        datetime.second(),
+        fraction
+    ))
+}
+
+fn format_time(micros: i64, micros_precision: bool) -> Result<String> {
+    if !(0..86_400_000_000).contains(&micros) {
+        return Err(Error::AvroParse {
+            message: format!("Invalid time of day {} micros", micros),
+        });
+    }
+    let seconds = micros / 1_000_000;
+    let fraction = if micros_precision {
+        format!("{:06}", micros % 1_000_000)
+    } else {
+        format!("{:03}", micros % 1_000_000 / 1000)
+    };
+    Ok(format!(
+        "{:02}:{:02}:{:02}.{}",
+        seconds / 3600,
+        seconds / 60 % 60,
+        seconds % 60,
+        fraction
+    ))
+}
+
+fn to_datetime(nanos: i128) -> Result<OffsetDateTime> {
+    OffsetDateTime::from_unix_timestamp_nanos(nanos).map_err(|err| Error::AvroParse {
+        message: format!("{}\n{}", "Invalid date or timestamp", err),
+    })
+}
+
+// decimals can exceed the size of any primitive type,
+// format the unscaled value without losing precision
+fn format_decimal(unscaled: &BigInt, scale: usize) -> String {
+    let sign = if unscaled.sign() == Sign::Minus { "-" } else { "" };
+    let digits = unscaled.magnitude().to_string();
+    if scale == 0 {
+        return format!("{}{}", sign, digits);
     }
+    let digits = format!("{:0>width$}", digits, width = scale + 1);
+    let (integer, fraction) = digits.split_at(digits.len() - scale);
+    format!("{}{}.{}", sign, integer, fraction)
 }
 
 #[cfg(test)]
 mod tests {
-    use std::sync::Arc;
+    use std::{collections::HashMap, sync::Arc, time::Instant};
 
-    use apache_avro::{to_avro_datum, types::Record, types::Value as AvroValue, Schema as ApacheAvroSchema, Writer};
+    use apache_avro::{
+        schema::Name, to_avro_datum, types::Record, types::Value as AvroValue, Days, Duration, Millis, Months,
+        Schema as ApacheAvroSchema, Writer,
+    };
     use async_trait::async_trait;
+    use num_bigint::BigInt;
 
-    use crate::lib::schema_registry::{Result, SchemaRegistryClient, Subject};
+    use crate::lib::schema_registry::{RawSchema, Result, Schema, SchemaRegistryClient, SchemaType, Subject};
 
-    use super::{get_schema_id, AvroParser};
+    use super::{
+        format_decimal, format_logical_value, get_schema_id, map, AvroEncoding, AvroParser, AvroReaderSchema,
+        LATEST_READER_SCHEMA_TTL,
+    };
     struct MockSchemaRegistry {
         schema: String,
+        // returned for the schema id 2
+        reader_schema: Option<String>,
     }
 
     #[async_trait]
@@ -190,8 +428,23 @@ mod tests {
         async fn get_subject(&self, _: &str) -> Result<Subject> {
             todo!()
         }
-        async fn get_schema_by_id(&self, _: i32) -> Result<ApacheAvroSchema> {
-            Ok(ApacheAvroSchema::parse_str(&self.schema).unwrap())
+        async fn get_schema_by_id(&self, id: i32) -> Result<ApacheAvroSchema> {
+            match (id, &self.reader_schema) {
+                (2, Some(reader_schema)) => Ok(ApacheAvroSchema::parse_str(reader_schema).unwrap()),
+                _ => Ok(ApacheAvroSchema::parse_str(&self.schema).unwrap()),
+            }
+        }
+        async fn get_raw_schema_by_id(&self, _: i32) -> Result<RawSchema> {
+            todo!()
+        }
+        async fn get_schema_version(&self, _: &str, _: Option<i32>) -> Result<Schema> {
+            Ok(Schema {
+                id: 2,
+                version: 2,
+                schema: self.reader_schema.clone().unwrap(),
+                schema_type: SchemaType::Avro,
+                references: vec![],
+            })
         }
         async fn delete_subject(&self, _: &str) -> Result<()> {
             todo!()
@@ -202,7 +455,10 @@ mod tests {
         }
     }
     fn get_sut(schema: String) -> AvroParser<MockSchemaRegistry> {
-        AvroParser::new(Arc::new(MockSchemaRegistry { schema }))
+        AvroParser::new(Arc::new(MockSchemaRegistry {
+            schema,
+            reader_schema: None,
+        }))
     }
 
     #[tokio::test]
@@ -240,7 +496,10 @@ mod tests {
         let mut raw: Vec<u8> = vec![0x00, 0x00, 0x00, 0x00, 0x00];
         raw.append(&mut encoded);
 
-        let res = get_sut(raw_schema.to_string()).parse_payload(&raw[..]).await.unwrap();
+        let res = get_sut(raw_schema.to_string())
+            .parse_payload(&raw[..], &AvroEncoding::Friendly, None)
+            .await
+            .unwrap();
 
         assert_eq!(
             res,
@@ -254,4 +513,333 @@ mod tests {
         let id = get_schema_id(&raw).unwrap();
         assert_eq!(id, 100037)
     }
+
+    #[tokio::test]
+    async fn test_logical_types_parsing() {
+        // the same record encoded with the underlying primitive types
+        let writer_schema = r#"
+    {
+        "fields": [
+            { "name": "date", "type": "int" },
+            { "name": "time_millis", "type": "int" },
+            { "name": "time_micros", "type": "long" },
+            { "name": "timestamp_millis", "type": "long" },
+            { "name": "timestamp_micros", "type": "long" },
+            { "name": "uuid", "type": "string" },
+            { "name": "decimal", "type": "bytes" },
+            { "name": "negative_decimal", "type": "bytes" },
+            { "name": "big_decimal", "type": "bytes" }
+        ],
+        "name": "sampleRecord",
+        "type": "record"
+    }
+"#;
+        let raw_schema = r#"
+    {
+        "fields": [
+            { "name": "date", "type": { "type": "int", "logicalType": "date" } },
+            { "name": "time_millis", "type": { "type": "int", "logicalType": "time-millis" } },
+            { "name": "time_micros", "type": { "type": "long", "logicalType": "time-micros" } },
+            { "name": "timestamp_millis", "type": { "type": "long", "logicalType": "timestamp-millis" } },
+            { "name": "timestamp_micros", "type": { "type": "long", "logicalType": "timestamp-micros" } },
+            { "name": "uuid", "type": { "type": "string", "logicalType": "uuid" } },
+            { "name": "decimal", "type": { "type": "bytes", "logicalType": "decimal", "precision": 5, "scale": 2 } },
+            { "name": "negative_decimal", "type": { "type": "bytes", "logicalType": "decimal", "precision": 5, "scale": 2 } },
+            { "name": "big_decimal", "type": { "type": "bytes", "logicalType": "decimal", "precision": 32, "scale": 2 } }
+        ],
+        "name": "sampleRecord",
+        "type": "record"
+    }
+"#;
+        let big_decimal = BigInt::parse_bytes(b"1000000000000000000000000000001", 10).unwrap();
+        let record = AvroValue::Record(vec![
+            ("date".into(), AvroValue::Int(19358)),
+            ("time_millis".into(), AvroValue::Int(3_600_000)),
+            ("time_micros".into(), AvroValue::Long(3_600_000_000)),
+            ("timestamp_millis".into(), AvroValue::Long(1_672_531_200_000)),
+            ("timestamp_micros".into(), AvroValue::Long(1_672_531_200_000_000)),
+            (
+                "uuid".into(),
+                AvroValue::String("550e8400-e29b-41d4-a716-446655440000".into()),
+            ),
+            (
+                "decimal".into(),
+                AvroValue::Bytes(BigInt::from(12345).to_signed_bytes_be()),
+            ),
+            (
+                "negative_decimal".into(),
+                AvroValue::Bytes(BigInt::from(-5).to_signed_bytes_be()),
+            ),
+            ("big_decimal".into(), AvroValue::Bytes(big_decimal.to_signed_bytes_be())),
+        ]);
+        let mut encoded = to_avro_datum(&ApacheAvroSchema::parse_str(writer_schema).unwrap(), record).unwrap();
+        let mut raw: Vec<u8> = vec![0x00, 0x00, 0x00, 0x00, 0x00];
+        raw.append(&mut encoded);
+
+        let res = get_sut(raw_schema.to_string())
+            .parse_payload(&raw[..], &AvroEncoding::Friendly, None)
+            .await
+            .unwrap();
+
+        assert_eq!(
+            res,
+            r#"{"big_decimal":"10000000000000000000000000000.01","date":19358,"decimal":"123.45","negative_decimal":"-0.05","time_micros":3600000000,"time_millis":3600000,"timestamp_micros":1672531200000000,"timestamp_millis":1672531200000,"uuid":"550e8400-e29b-41d4-a716-446655440000"}"#
+        );
+
+        // apache-avro is unable to parse a duration schema, map the value directly
+        let duration = AvroValue::Duration(Duration::new(Months::new(1), Days::new(2), Millis::new(3)));
+        let res = map(
+            &duration,
+            &ApacheAvroSchema::Duration,
+            &AvroEncoding::Friendly,
+            &mut HashMap::new(),
+        )
+        .unwrap();
+        assert_eq!(res, "1 months 2 days 3 millis");
+    }
+
+    #[tokio::test]
+    async fn test_avro_json_encoding() {
+        let raw_schema = r#"
+    {
+        "fields": [
+            { "name": "null_union", "type": ["null", "string"] },
+            { "name": "string_union", "type": ["null", "string"] },
+            { "name": "record_union", "type": ["null", { "type": "record", "name": "inner", "namespace": "com.example", "fields": [{ "name": "id", "type": "long" }] }] },
+            { "name": "bytes_field", "type": "bytes" },
+            { "name": "fixed_field", "type": { "type": "fixed", "name": "two_bytes", "size": 2 } },
+            { "name": "decimal_field", "type": { "type": "bytes", "logicalType": "decimal", "precision": 5, "scale": 2 } }
+        ],
+        "name": "sampleRecord",
+        "type": "record"
+    }
+"#;
+        let schema = ApacheAvroSchema::parse_str(raw_schema).unwrap();
+        let record = AvroValue::Record(vec![
+            ("null_union".into(), AvroValue::Union(0, Box::new(AvroValue::Null))),
+            (
+                "string_union".into(),
+                AvroValue::Union(1, Box::new(AvroValue::String("text".into()))),
+            ),
+            (
+                "record_union".into(),
+                AvroValue::Union(1, Box::new(AvroValue::Record(vec![("id".into(), AvroValue::Long(7))]))),
+            ),
+            ("bytes_field".into(), AvroValue::Bytes(vec![0x01, 0x41, 0xff])),
+            ("fixed_field".into(), AvroValue::Fixed(2, vec![0x41, 0xe9])),
+            ("decimal_field".into(), AvroValue::Decimal(vec![0x30, 0x39].into())),
+        ]);
+        let mut encoded = to_avro_datum(&schema, record).unwrap();
+        let mut raw: Vec<u8> = vec![0x00, 0x00, 0x00, 0x00, 0x00];
+        raw.append(&mut encoded);
+
+        let res = get_sut(raw_schema.to_string())
+            .parse_payload(&raw[..], &AvroEncoding::AvroJson, None)
+            .await
+            .unwrap();
+
+        assert_eq!(
+            serde_json::from_str::<serde_json::Value>(&res).unwrap(),
+            serde_json::json!({
+                "null_union": null,
+                "string_union": { "string": "text" },
+                "record_union": { "com.example.inner": { "id": 7 } },
+                "bytes_field": "\u{1}A\u{ff}",
+                "fixed_field": "A\u{e9}",
+                "decimal_field": "09"
+            })
+        )
+    }
+
+    #[tokio::test]
+    async fn test_resolve_with_reader_schema() {
+        let writer_schema = r#"{ "type": "record", "name": "sampleRecord", "fields": [
+            { "name": "id", "type": "long" },
+            { "name": "removed", "type": "string" }
+        ] }"#;
+        let reader_schema = r#"{ "type": "record", "name": "sampleRecord", "fields": [
+            { "name": "id", "type": "long" },
+            { "name": "added", "type": "string", "default": "unknown" }
+        ] }"#;
+        let record = AvroValue::Record(vec![
+            ("id".into(), AvroValue::Long(7)),
+            ("removed".into(), AvroValue::String("old".into())),
+        ]);
+        let mut encoded = to_avro_datum(&ApacheAvroSchema::parse_str(writer_schema).unwrap(), record).unwrap();
+        let mut raw: Vec<u8> = vec![0x00, 0x00, 0x00, 0x00, 0x01];
+        raw.append(&mut encoded);
+        let sut = AvroParser::new(Arc::new(MockSchemaRegistry {
+            schema: writer_schema.into(),
+            reader_schema: Some(reader_schema.into()),
+        }));
+        let latest = AvroReaderSchema {
+            subject: "sample-value".into(),
+            version: None,
+        };
+
+        let writer_res = sut
+            .parse_payload(&raw[..], &AvroEncoding::Friendly, None)
+            .await
+            .unwrap();
+        let reader_res = sut
+            .parse_payload(&raw[..], &AvroEncoding::Friendly, Some(&latest))
+            .await
+            .unwrap();
+
+        assert_eq!(writer_res, r#"{"id":7,"removed":"old"}"#);
+        assert_eq!(reader_res, r#"{"added":"unknown","id":7}"#);
+    }
+
+    #[test]
+    fn test_references_to_union_variants() {
+        let schema = ApacheAvroSchema::parse_str(
+            r#"{"type": "record", "name": "sample", "fields": [
+                {"name": "first", "type": ["null",
+                    {"type": "enum", "name": "Color", "symbols": ["RED", "GREEN"]},
+                    {"type": "fixed", "name": "Hash", "size": 2}
+                ]},
+                {"name": "color", "type": "Color"},
+                {"name": "hash", "type": "Hash"}
+            ]}"#,
+        )
+        .unwrap();
+        let value = AvroValue::Record(vec![
+            ("first".into(), AvroValue::Union(0, Box::new(AvroValue::Null))),
+            ("color".into(), AvroValue::Enum(1, "GREEN".into())),
+            ("hash".into(), AvroValue::Fixed(2, vec![0x01, 0x02])),
+        ]);
+
+        let res = map(&value, &schema, &AvroEncoding::Friendly, &mut HashMap::new()).unwrap();
+
+        assert_eq!(
+            res,
+            serde_json::json!({"first": null, "color": "GREEN", "hash": [1, 2]})
+        );
+    }
+
+    #[tokio::test]
+    async fn test_refresh_the_latest_reader_schema() {
+        let sut = AvroParser::new(Arc::new(MockSchemaRegistry {
+            schema: r#""long""#.into(),
+            reader_schema: Some(r#""long""#.into()),
+        }));
+        let latest = AvroReaderSchema {
+            subject: "sample-value".into(),
+            version: None,
+        };
+        let explicit = AvroReaderSchema {
+            version: Some(2),
+            ..latest.clone()
+        };
+        let latest_res = sut.get_reader_schema(&latest).await.unwrap();
+        let explicit_res = sut.get_reader_schema(&explicit).await.unwrap();
+        assert!(Arc::ptr_eq(&latest_res, &sut.get_reader_schema(&latest).await.unwrap()));
+        for (_, retrieved_at) in sut.reader_schema_cache.lock().await.values_mut() {
+            *retrieved_at = Instant::now() - LATEST_READER_SCHEMA_TTL;
+        }
+
+        assert!(!Arc::ptr_eq(
+            &latest_res,
+            &sut.get_reader_schema(&latest).await.unwrap()
+        ));
+        assert!(Arc::ptr_eq(
+            &explicit_res,
+            &sut.get_reader_schema(&explicit).await.unwrap()
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_invalid_record_is_an_error() {
+        let raw_schema =
+            r#"{ "type": "record", "name": "sampleRecord", "fields": [{ "name": "text", "type": "string" }] }"#;
+        // the string length is bigger than the payload
+        let raw: Vec<u8> = vec![0x00, 0x00, 0x00, 0x00, 0x00, 0x10, 0x41];
+        assert!(get_sut(raw_schema.to_string())
+            .parse_payload(&raw[..], &AvroEncoding::Friendly, None)
+            .await
+            .is_err());
+    }
+
+    #[test]
+    fn test_unexpected_value_is_an_error() {
+        let encoding = AvroEncoding::Friendly;
+        assert!(map(
+            &AvroValue::Int(1),
+            &ApacheAvroSchema::String,
+            &encoding,
+            &mut HashMap::new()
+        )
+        .is_err());
+        let missing_ref = ApacheAvroSchema::Ref {
+            name: Name::new("missing").unwrap(),
+        };
+        assert!(map(&AvroValue::Null, &missing_ref, &encoding, &mut HashMap::new()).is_err());
+    }
+
+    #[test]
+    fn test_readable_logical_types() {
+        let cases = [
+            (AvroValue::Date(19358), "2023-01-01"),
+            (AvroValue::TimeMillis(3_723_004), "01:02:03.004"),
+            (AvroValue::TimeMicros(3_723_004_005), "01:02:03.004005"),
+            (
+                AvroValue::TimestampMillis(1_672_531_200_123),
+                "2023-01-01T00:00:00.123Z",
+            ),
+            (AvroValue::TimestampMicros(-1), "1969-12-31T23:59:59.999999Z"),
+        ];
+        for (value, expected) in cases {
+            assert_eq!(format_logical_value(&value).unwrap(), Some(expected.into()));
+        }
+        assert_eq!(format_logical_value(&AvroValue::Long(1)).unwrap(), None);
+        assert!(format_logical_value(&AvroValue::TimeMillis(-1)).is_err());
+    }
+
+    #[test]
+    fn test_readable_sibling_fields() {
+        let schema = ApacheAvroSchema::parse_str(
+            r#"{"type": "record", "name": "sample", "fields": [
+                {"name": "day", "type": {"type": "int", "logicalType": "date"}},
+                {"name": "at", "type": ["null", {"type": "long", "logicalType": "timestamp-millis"}]},
+                {"name": "id", "type": "long"}
+            ]}"#,
+        )
+        .unwrap();
+        let value = AvroValue::Record(vec![
+            ("day".into(), AvroValue::Date(19358)),
+            (
+                "at".into(),
+                AvroValue::Union(1, Box::new(AvroValue::TimestampMillis(1_672_531_200_123))),
+            ),
+            ("id".into(), AvroValue::Long(1)),
+        ]);
+
+        let readable = map(&value, &schema, &AvroEncoding::Readable, &mut HashMap::new()).unwrap();
+        let friendly = map(&value, &schema, &AvroEncoding::Friendly, &mut HashMap::new()).unwrap();
+
+        // the numeric values are kept next to the readable ones
+        assert_eq!(
+            readable,
+            serde_json::json!({
+                "day": 19358,
+                "day_readable": "2023-01-01",
+                "at": 1_672_531_200_123_i64,
+                "at_readable": "2023-01-01T00:00:00.123Z",
+                "id": 1
+            })
+        );
+        assert_eq!(
+            friendly,
+            serde_json::json!({"day": 19358, "at": 1_672_531_200_123_i64, "id": 1})
+        );
+    }
+
+    #[test]
+    fn test_format_decimal() {
+        assert_eq!(format_decimal(&BigInt::from(12345), 0), "12345");
+        assert_eq!(format_decimal(&BigInt::from(12345), 2), "123.45");
+        assert_eq!(format_decimal(&BigInt::from(5), 3), "0.005");
+        assert_eq!(format_decimal(&BigInt::from(-5), 3), "-0.005");
+        assert_eq!(format_decimal(&BigInt::from(0), 1), "0.0");
+    }
 }
@@ -0,0 +1,170 @@
+use std::{collections::HashMap, sync::Arc};
+
+use futures::lock::Mutex;
+use jsonschema::JSONSchema;
+use serde_json::Value as JsonValue;
+
+use crate::lib::{
+    error::{Error, Result},
+    schema_registry::{CachedSchemaRegistry, SchemaRegistryClient, SchemaType},
+};
+
+pub struct JsonSchemaParser<C = CachedSchemaRegistry>
+where
+    C: SchemaRegistryClient + Send + Sync,
+{
+    schema_registry_client: Arc<C>,
+    schema_cache: Arc<Mutex<HashMap<i32, Arc<JSONSchema>>>>,
+}
+
+impl<C> JsonSchemaParser<C>
+where
+    C: SchemaRegistryClient + Send + Sync,
+{
+    pub fn new(schema_registry_client: Arc<C>) -> Self {
+        JsonSchemaParser {
+            schema_registry_client,
+            schema_cache: Arc::new(Mutex::new(HashMap::new())),
+        }
+    }
+
+    // returns the json payload without the framing and, if validate is true,
+    // the list of errors found validating the payload against the schema
+    pub async fn parse_payload(&self, raw: &[u8], validate: bool) -> Result<(String, Option<Vec<String>>)> {
+        if raw.len() <= 5 || raw[0] != 0x00 {
+            return Err(Error::JsonSchemaParse {
+                message: "Supported json messages should start with 0x00 follow by the schema id (4 bytes)".into(),
+            });
+        }
+        let id = i32::from_be_bytes([raw[1], raw[2], raw[3], raw[4]]);
+        let payload = String::from_utf8(raw[5..].to_vec()).map_err(|err| Error::JsonSchemaParse {
+            message: format!("{}\n{}", "Invalid UTF-8 json payload", err),
+        })?;
+        let json: JsonValue = serde_json::from_str(&payload).map_err(|err| Error::JsonSchemaParse {
+            message: format!("{}\n{}", "Unable to parse the json payload", err),
+        })?;
+        // make sure the record has been produced with a json schema
+        let schema = self.get_schema(id).await?;
+        let validation_errors = if validate {
+            Some(match schema.validate(&json) {
+                Ok(_) => vec![],
+                Err(errors) => errors.map(|err| format!("{}: {}", err.instance_path, err)).collect(),
+            })
+        } else {
+            None
+        };
+        Ok((payload, validation_errors))
+    }
+
+    async fn get_schema(&self, id: i32) -> Result<Arc<JSONSchema>> {
+        let mut cache = self.schema_cache.lock().await;
+        if let Some(cached) = cache.get(&id) {
+            return Ok(cached.clone());
+        }
+        let schema = self
+            .schema_registry_client
+            .get_raw_schema_by_id(id)
+            .await
+            .map_err(|err| Error::JsonSchemaParse {
+                message: format!(
+                    "{}\n{}",
+                    "Unable to retrieve the schema from schema registry",
+                    err.to_string()
+                ),
+            })?;
+        if schema.schema_type != SchemaType::Json {
+            return Err(Error::JsonSchemaParse {
+                message: format!("The schema {} is not a json schema ({:?})", id, schema.schema_type),
+            });
+        }
+        let map_err = |err: String| Error::JsonSchemaParse {
+            message: format!("{}\n{}", "Unable to parse the json schema", err),
+        };
+        let json_schema: JsonValue = serde_json::from_str(&schema.schema).map_err(|err| map_err(err.to_string()))?;
+        let compiled = Arc::new(JSONSchema::compile(&json_schema).map_err(|err| map_err(err.to_string()))?);
+        cache.insert(id, compiled.clone());
+        Ok(compiled)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use apache_avro::Schema as ApacheAvroSchema;
+    use async_trait::async_trait;
+
+    use crate::lib::schema_registry::{RawSchema, Result, Schema, SchemaRegistryClient, SchemaType, Subject};
+
+    use super::JsonSchemaParser;
+
+    struct MockSchemaRegistry {}
+
+    #[async_trait]
+    impl SchemaRegistryClient for MockSchemaRegistry {
+        async fn list_subjects(&self) -> Result<Vec<String>> {
+            todo!()
+        }
+        async fn get_subject(&self, _: &str) -> Result<Subject> {
+            todo!()
+        }
+        async fn get_schema_by_id(&self, _: i32) -> Result<ApacheAvroSchema> {
+            todo!()
+        }
+        async fn get_raw_schema_by_id(&self, _: i32) -> Result<RawSchema> {
+            Ok(RawSchema {
+                schema: r#"{
+                    "type": "object",
+                    "properties": {
+                        "name": { "type": "string" },
+                        "age": { "type": "integer" }
+                    },
+                    "required": ["name"]
+                }"#
+                .into(),
+                schema_type: SchemaType::Json,
+                references: vec![],
+            })
+        }
+        async fn get_schema_version(&self, _: &str, _: Option<i32>) -> Result<Schema> {
+            todo!()
+        }
+        async fn delete_subject(&self, _: &str) -> Result<()> {
+            todo!()
+        }
+        async fn delete_version(&self, _: &str, _: i32) -> Result<()> {
+            todo!()
+        }
+    }
+
+    fn get_raw(json: &str) -> Vec<u8> {
+        let mut raw: Vec<u8> = vec![0x00, 0x00, 0x00, 0x00, 0x01];
+        raw.extend(json.as_bytes());
+        raw
+    }
+
+    #[tokio::test]
+    async fn test_strip_framing_and_validate() {
+        let sut = JsonSchemaParser::new(Arc::new(MockSchemaRegistry {}));
+
+        let (payload, errors) = sut
+            .parse_payload(&get_raw(r#"{"name":"bob","age":42}"#), true)
+            .await
+            .unwrap();
+        assert_eq!(payload, r#"{"name":"bob","age":42}"#);
+        assert_eq!(errors, Some(vec![]));
+
+        let (_, errors) = sut.parse_payload(&get_raw(r#"{"age":"42"}"#), true).await.unwrap();
+        assert_eq!(errors.unwrap().len(), 2);
+
+        let (_, errors) = sut.parse_payload(&get_raw(r#"{"age":"42"}"#), false).await.unwrap();
+        assert_eq!(errors, None);
+    }
+
+    #[tokio::test]
+    async fn test_invalid_json_payload() {
+        let sut = JsonSchemaParser::new(Arc::new(MockSchemaRegistry {}));
+        assert!(sut.parse_payload(&get_raw("not a json"), true).await.is_err());
+        assert!(sut.parse_payload(r#"{"name":"bob"}"#.as_bytes(), true).await.is_err());
+    }
+}
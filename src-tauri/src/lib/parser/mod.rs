@@ -1,5 +1,8 @@
 mod avro_parser;
+mod json_schema_parser;
+mod protobuf_parser;
 mod record_parser;
 mod string_parser;
 
+pub use avro_parser::{AvroEncoding, AvroParser, AvroReaderSchema};
 pub use record_parser::{Parser, ParserMode, RecordParser};
@@ -0,0 +1,377 @@
+use std::{
+    collections::HashMap,
+    fs,
+    path::{Component, Path},
+    sync::Arc,
+};
+
+use futures::lock::Mutex;
+use protobuf::{
+    reflect::{FileDescriptor, MessageDescriptor, ReflectFieldRef, ReflectValueRef, RuntimeFieldType, RuntimeType},
+    MessageDyn,
+};
+use protobuf_parse::Parser;
+use serde_json::{json, Map, Value as JsonValue};
+
+use crate::lib::{
+    error::{Error, Result},
+    schema_registry::{CachedSchemaRegistry, SchemaRegistryClient, SchemaType},
+};
+
+pub struct ProtobufParser<C = CachedSchemaRegistry>
+where
+    C: SchemaRegistryClient + Send + Sync,
+{
+    schema_registry_client: Arc<C>,
+    descriptor_cache: Arc<Mutex<HashMap<i32, FileDescriptor>>>,
+}
+
+impl<C> ProtobufParser<C>
+where
+    C: SchemaRegistryClient + Send + Sync,
+{
+    pub fn new(schema_registry_client: Arc<C>) -> Self {
+        ProtobufParser {
+            schema_registry_client,
+            descriptor_cache: Arc::new(Mutex::new(HashMap::new())),
+        }
+    }
+
+    pub async fn parse_payload(&self, raw: &[u8]) -> Result<String> {
+        if raw.len() < 6 || raw[0] != 0x00 {
+            return Err(Error::ProtobufParse {
+                message: "Supported protobuf messages should start with 0x00 follow by the schema id (4 bytes)".into(),
+            });
+        }
+        let id = i32::from_be_bytes([raw[1], raw[2], raw[3], raw[4]]);
+        let mut data = &raw[5..];
+        let indexes = read_message_indexes(&mut data)?;
+        let file_descriptor = self.get_file_descriptor(id).await?;
+        let descriptor = get_message_descriptor(&file_descriptor, &indexes)?;
+        let message = descriptor.parse_from_bytes(data).map_err(|err| Error::ProtobufParse {
+            message: format!("{}\n{}", "Unable to parse the protobuf record", err),
+        })?;
+        let json = map_message(&*message);
+        let res = serde_json::to_string(&json).map_err(|err| Error::ProtobufParse {
+            message: format!("{}\n{}", "Unable to map the protobuf record to json", err),
+        })?;
+        Ok(res)
+    }
+
+    async fn get_file_descriptor(&self, id: i32) -> Result<FileDescriptor> {
+        // the lock is not held while querying the schema registry
+        if let Some(cached) = self.descriptor_cache.lock().await.get(&id) {
+            return Ok(cached.clone());
+        }
+        let map_err = |err: String| Error::ProtobufParse {
+            message: format!("{}\n{}", "Unable to retrieve the schema from schema registry", err),
+        };
+        let schema = self
+            .schema_registry_client
+            .get_raw_schema_by_id(id)
+            .await
+            .map_err(|err| map_err(err.to_string()))?;
+        if schema.schema_type != SchemaType::Protobuf {
+            return Err(Error::ProtobufParse {
+                message: format!("The schema {} is not a protobuf schema ({:?})", id, schema.schema_type),
+            });
+        }
+        // retrieve all the imported files, including the transitive ones
+        let mut files = HashMap::new();
+        let mut references = schema.references;
+        while let Some(reference) = references.pop() {
+            if files.contains_key(&reference.name) {
+                continue;
+            }
+            let referenced = self
+                .schema_registry_client
+                .get_schema_version(&reference.subject, Some(reference.version))
+                .await
+                .map_err(|err| map_err(err.to_string()))?;
+            references.extend(referenced.references);
+            files.insert(reference.name, referenced.schema);
+        }
+        let name = format!("schema_{}.proto", id);
+        files.insert(name.clone(), schema.schema);
+        let file_descriptor = build_file_descriptor(&name, files)?;
+        self.descriptor_cache.lock().await.insert(id, file_descriptor.clone());
+        Ok(file_descriptor)
+    }
+}
+
+// the pure rust parser reads the .proto files from the filesystem, so the schema
+// and its references are written to a temporary directory before parsing them
+fn build_file_descriptor(name: &str, files: HashMap<String, String>) -> Result<FileDescriptor> {
+    let map_err = |err: String| Error::ProtobufParse {
+        message: format!("{}\n{}", "Unable to parse the protobuf schema", err),
+    };
+    let dir = tempfile::tempdir()?;
+    for (path, content) in files.iter() {
+        // the reference names come from the schema registry, they must stay in the temporary directory
+        let is_relative = Path::new(path).components().all(|c| matches!(c, Component::Normal(_)));
+        if path.is_empty() || !is_relative {
+            return Err(map_err(format!("Invalid reference path {}", path)));
+        }
+        let path = dir.path().join(path);
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        fs::write(path, content)?;
+    }
+    let protos = Parser::new()
+        .pure()
+        .include(dir.path())
+        .input(dir.path().join(name))
+        .parse_and_typecheck()
+        .map_err(|err| map_err(err.to_string()))?
+        .file_descriptors;
+    FileDescriptor::new_dynamic_fds(protos, &[])
+        .map_err(|err| map_err(err.to_string()))?
+        .into_iter()
+        .find(|f| f.name() == name)
+        .ok_or_else(|| map_err(format!("Missing file descriptor {}", name)))
+}
+
+// the confluent wire format adds, after the schema id, the indexes of the message type
+// in the schema, encoded as a zig-zag varint array. A single 0 is used for the first message.
+fn read_message_indexes(data: &mut &[u8]) -> Result<Vec<usize>> {
+    let count = read_varint(data)?;
+    if count == 0 {
+        return Ok(vec![0]);
+    }
+    let mut indexes = Vec::new();
+    for _ in 0..count {
+        let index = read_varint(data)?;
+        indexes.push(usize::try_from(index).map_err(|_| Error::ProtobufParse {
+            message: format!("Invalid message index {}", index),
+        })?);
+    }
+    Ok(indexes)
+}
+
+fn read_varint(data: &mut &[u8]) -> Result<i64> {
+    let mut value: u64 = 0;
+    for (i, byte) in data.iter().enumerate().take(10) {
+        value |= ((byte & 0x7f) as u64) << (7 * i);
+        if byte & 0x80 == 0 {
+            *data = &data[i + 1..];
+            // zig-zag decoding
+            return Ok((value >> 1) as i64 ^ -((value & 1) as i64));
+        }
+    }
+    Err(Error::ProtobufParse {
+        message: "Invalid record. Unable to read the message indexes.".into(),
+    })
+}
+
+fn get_message_descriptor(file_descriptor: &FileDescriptor, indexes: &[usize]) -> Result<MessageDescriptor> {
+    let mut messages: Vec<_> = file_descriptor.messages().collect();
+    let mut descriptor = None;
+    for index in indexes {
+        let message = messages.get(*index).cloned().ok_or_else(|| Error::ProtobufParse {
+            message: format!("Message index {:?} not found in the schema", indexes),
+        })?;
+        messages = message.nested_messages().collect();
+        descriptor = Some(message);
+    }
+    descriptor.ok_or_else(|| Error::ProtobufParse {
+        message: "Missing message index".into(),
+    })
+}
+
+fn map_message(message: &dyn MessageDyn) -> JsonValue {
+    let mut json_map = Map::new();
+    for field in message.descriptor_dyn().fields() {
+        let value = match field.get_reflect(message) {
+            ReflectFieldRef::Optional(v) => match v.value() {
+                Some(v) => map_value(v),
+                // only the field set in a oneof is shown
+                None if field.containing_oneof().is_some() => continue,
+                None => match field.runtime_field_type() {
+                    RuntimeFieldType::Singular(RuntimeType::Message(_)) => JsonValue::Null,
+                    _ => map_value(field.get_singular_field_or_default(message)),
+                },
+            },
+            ReflectFieldRef::Repeated(v) => JsonValue::Array(v.into_iter().map(map_value).collect()),
+            ReflectFieldRef::Map(v) => {
+                JsonValue::Object((&v).into_iter().map(|(k, v)| (map_key(k), map_value(v))).collect())
+            }
+        };
+        json_map.insert(field.json_name().into(), value);
+    }
+    JsonValue::Object(json_map)
+}
+
+fn map_value(value: ReflectValueRef) -> JsonValue {
+    match value {
+        ReflectValueRef::U32(v) => json!(v),
+        ReflectValueRef::U64(v) => json!(v),
+        ReflectValueRef::I32(v) => json!(v),
+        ReflectValueRef::I64(v) => json!(v),
+        ReflectValueRef::F32(v) => json!(v),
+        ReflectValueRef::F64(v) => json!(v),
+        ReflectValueRef::Bool(v) => json!(v),
+        ReflectValueRef::String(v) => json!(v),
+        ReflectValueRef::Bytes(v) => json!(v),
+        ReflectValueRef::Enum(descriptor, v) => match descriptor.value_by_number(v) {
+            Some(enum_value) => json!(enum_value.name()),
+            None => json!(v),
+        },
+        ReflectValueRef::Message(v) => map_message(&*v),
+    }
+}
+
+fn map_key(key: ReflectValueRef) -> String {
+    match key {
+        ReflectValueRef::String(v) => v.into(),
+        k => map_value(k).to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{collections::HashMap, sync::Arc};
+
+    use apache_avro::Schema as ApacheAvroSchema;
+    use async_trait::async_trait;
+
+    use crate::lib::schema_registry::{
+        RawSchema, Result, Schema, SchemaReference, SchemaRegistryClient, SchemaType, Subject,
+    };
+
+    use super::{build_file_descriptor, read_message_indexes, ProtobufParser};
+
+    struct MockSchemaRegistry {}
+
+    #[async_trait]
+    impl SchemaRegistryClient for MockSchemaRegistry {
+        async fn list_subjects(&self) -> Result<Vec<String>> {
+            todo!()
+        }
+        async fn get_subject(&self, _: &str) -> Result<Subject> {
+            todo!()
+        }
+        async fn get_schema_by_id(&self, _: i32) -> Result<ApacheAvroSchema> {
+            todo!()
+        }
+        async fn get_raw_schema_by_id(&self, _: i32) -> Result<RawSchema> {
+            Ok(RawSchema {
+                schema: r#"
+                syntax = "proto3";
+                package com.example;
+                import "address.proto";
+                message Unused {}
+                message Person {
+                    string name = 1;
+                    int64 age = 2;
+                    Address address = 3;
+                    repeated string tags = 4;
+                    Status status = 5;
+                    oneof contact {
+                        string email = 6;
+                        string phone = 7;
+                    }
+                    message Nested {
+                        bool flag = 1;
+                    }
+                    enum Status {
+                        UNKNOWN = 0;
+                        ACTIVE = 1;
+                    }
+                }"#
+                .into(),
+                schema_type: SchemaType::Protobuf,
+                references: vec![SchemaReference {
+                    name: "address.proto".into(),
+                    subject: "address".into(),
+                    version: 1,
+                }],
+            })
+        }
+        async fn get_schema_version(&self, _: &str, _: Option<i32>) -> Result<Schema> {
+            Ok(Schema {
+                id: 2,
+                version: 1,
+                schema: r#"
+                syntax = "proto3";
+                package com.example;
+                message Address {
+                    string city = 1;
+                }"#
+                .into(),
+                schema_type: SchemaType::Protobuf,
+                references: vec![],
+            })
+        }
+        async fn delete_subject(&self, _: &str) -> Result<()> {
+            todo!()
+        }
+        async fn delete_version(&self, _: &str, _: i32) -> Result<()> {
+            todo!()
+        }
+    }
+
+    fn get_sut() -> ProtobufParser<MockSchemaRegistry> {
+        ProtobufParser::new(Arc::new(MockSchemaRegistry {}))
+    }
+
+    #[tokio::test]
+    async fn test_parse_protobuf_record() {
+        // magic byte + 4 id bytes + message indexes [1]
+        let mut raw: Vec<u8> = vec![0x00, 0x00, 0x00, 0x00, 0x01, 0x02, 0x02];
+        raw.extend([0x0a, 0x03, b'b', b'o', b'b']); // name
+        raw.extend([0x10, 0x2a]); // age
+        raw.extend([0x1a, 0x05, 0x0a, 0x03, b'r', b'o', b'm']); // address
+        raw.extend([0x22, 0x01, b'a', 0x22, 0x01, b'b']); // tags
+        raw.extend([0x28, 0x01]); // status
+        raw.extend([0x3a, 0x01, b'1']); // phone
+
+        let res = get_sut().parse_payload(&raw).await.unwrap();
+
+        assert_eq!(
+            res,
+            r#"{"address":{"city":"rom"},"age":42,"name":"bob","phone":"1","status":"ACTIVE","tags":["a","b"]}"#
+        )
+    }
+
+    #[tokio::test]
+    async fn test_parse_nested_message() {
+        // message indexes [1, 0] (Person.Nested), defaults are shown
+        let raw: Vec<u8> = vec![0x00, 0x00, 0x00, 0x00, 0x01, 0x04, 0x02, 0x00];
+        let res = get_sut().parse_payload(&raw).await.unwrap();
+        assert_eq!(res, r#"{"flag":false}"#)
+    }
+
+    #[test]
+    fn test_read_message_indexes() {
+        let raw: Vec<u8> = vec![0x00, 0xff];
+        let mut data = &raw[..];
+        assert_eq!(read_message_indexes(&mut data).unwrap(), vec![0]);
+        assert_eq!(data, &[0xff]);
+
+        let raw: Vec<u8> = vec![0x04, 0x02, 0xac, 0x02];
+        let mut data = &raw[..];
+        assert_eq!(read_message_indexes(&mut data).unwrap(), vec![1, 150]);
+        assert!(data.is_empty());
+    }
+
+    #[test]
+    fn test_invalid_reference_path() {
+        for path in [
+            "../outside.proto",
+            "/tmp/absolute.proto",
+            "nested/../../outside.proto",
+            "./local.proto",
+        ] {
+            let files = HashMap::from([
+                ("main.proto".to_string(), r#"syntax = "proto3";"#.to_string()),
+                (path.to_string(), r#"syntax = "proto3";"#.to_string()),
+            ]);
+            assert!(
+                build_file_descriptor("main.proto", files).is_err(),
+                "{} should fail",
+                path
+            );
+        }
+    }
+}
@@ -1,30 +1,74 @@
-use std::sync::Arc;
+use std::{collections::HashMap, sync::Arc};
 
 use async_trait::async_trait;
+use serde::{Deserialize, Serialize};
 
 use crate::lib::{
+    configuration::TopicSettings,
     error::{Error, Result},
-    schema_registry::{CachedSchemaRegistry, SchemaRegistryClient},
-    types::{ParsedKafkaRecord, RawKafkaRecord},
+    schema_registry::{CachedSchemaRegistry, SchemaRegistryClient, SchemaType},
+    types::{ParsedKafkaRecord, RawKafkaRecord, RecordFormat},
 };
 
-use super::{avro_parser::AvroParser, string_parser::parse_string};
+use super::{
+    json_schema_parser::JsonSchemaParser,
+    protobuf_parser::ProtobufParser,
+    string_parser::{parse_base64, parse_hex, parse_json, parse_string},
+    AvroParser, AvroReaderSchema,
+};
 
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq, Eq)]
 pub enum ParserMode {
+    // use the schema registry framing, if any, to pick the format of each part of the record
+    #[default]
+    Auto,
     String,
     Avro,
+    Protobuf,
+    JsonSchema {
+        validate: bool,
+    },
+    // plain json, without the schema registry framing
+    Json,
+    Hex,
+    Base64,
 }
 
 #[async_trait]
 pub trait Parser {
-    async fn parse_record(&self, record: &RawKafkaRecord, mode: ParserMode) -> Result<ParsedKafkaRecord>;
+    async fn parse_record(&self, record: &RawKafkaRecord, settings: &TopicSettings) -> Result<ParsedKafkaRecord>;
 }
 
 pub struct RecordParser<C = CachedSchemaRegistry>
 where
     C: SchemaRegistryClient + Send + Sync,
 {
+    schema_registry_client: Option<Arc<C>>,
     avro_parser: Option<AvroParser<C>>,
+    protobuf_parser: Option<ProtobufParser<C>>,
+    json_schema_parser: Option<JsonSchemaParser<C>>,
+}
+
+// decoded key or payload
+struct ParsedPart {
+    value: String,
+    format: RecordFormat,
+    validation_errors: Option<Vec<String>>,
+    parse_error: Option<String>,
+    // id of the registry schema used to decode the part
+    schema_id: Option<i32>,
+}
+
+impl ParsedPart {
+    fn new(value: String, format: RecordFormat) -> Self {
+        ParsedPart {
+            value,
+            format,
+            validation_errors: None,
+            parse_error: None,
+            schema_id: None,
+        }
+    }
 }
 
 impl<C> RecordParser<C>
@@ -33,9 +77,120 @@ where
 {
     pub fn new(schema_registry_client: Option<Arc<C>>) -> Self {
         RecordParser {
-            avro_parser: schema_registry_client.map(|client| AvroParser::new(client)),
+            avro_parser: schema_registry_client.clone().map(|client| AvroParser::new(client)),
+            protobuf_parser: schema_registry_client.clone().map(ProtobufParser::new),
+            json_schema_parser: schema_registry_client.clone().map(JsonSchemaParser::new),
+            schema_registry_client,
+        }
+    }
+
+    // never fails: if the part can't be decoded, the lossy string is
+    // returned along with the parse error
+    async fn parse_part(
+        &self,
+        raw: &[u8],
+        mode: &ParserMode,
+        reader_schema: Option<&AvroReaderSchema>,
+        settings: &TopicSettings,
+    ) -> ParsedPart {
+        let mode = match mode {
+            ParserMode::Auto => match self.detect_mode(raw).await {
+                Ok(mode) => mode,
+                // the part is stored as a string, with the registry error
+                Err(err) => {
+                    return ParsedPart {
+                        parse_error: Some(err),
+                        ..ParsedPart::new(parse_string(raw), RecordFormat::String)
+                    }
+                }
+            },
+            _ => mode.clone(),
+        };
+        let schema_id = match mode {
+            ParserMode::Avro | ParserMode::Protobuf | ParserMode::JsonSchema { .. } => get_schema_id(raw),
+            _ => None,
+        };
+        match self.parse_part_with(raw, &mode, reader_schema, settings).await {
+            Ok(part) => ParsedPart { schema_id, ..part },
+            Err(err) => ParsedPart {
+                parse_error: Some(err.to_string()),
+                schema_id,
+                ..ParsedPart::new(parse_string(raw), RecordFormat::String)
+            },
         }
     }
+
+    async fn parse_part_with(
+        &self,
+        raw: &[u8],
+        mode: &ParserMode,
+        reader_schema: Option<&AvroReaderSchema>,
+        settings: &TopicSettings,
+    ) -> Result<ParsedPart> {
+        match mode {
+            ParserMode::Auto | ParserMode::String => Ok(ParsedPart::new(parse_string(raw), RecordFormat::String)),
+            ParserMode::Avro => {
+                let avro_parser = self.avro_parser.as_ref().ok_or(Error::AvroParse {
+                    message: "Missing avro parser".into(),
+                })?;
+                Ok(ParsedPart::new(
+                    avro_parser
+                        .parse_payload(raw, &settings.avro_encoding, reader_schema)
+                        .await?,
+                    RecordFormat::Avro,
+                ))
+            }
+            ParserMode::Protobuf => {
+                let protobuf_parser = self.protobuf_parser.as_ref().ok_or(Error::ProtobufParse {
+                    message: "Missing protobuf parser".into(),
+                })?;
+                Ok(ParsedPart::new(
+                    protobuf_parser.parse_payload(raw).await?,
+                    RecordFormat::Protobuf,
+                ))
+            }
+            ParserMode::JsonSchema { validate } => {
+                let json_schema_parser = self.json_schema_parser.as_ref().ok_or(Error::JsonSchemaParse {
+                    message: "Missing json schema parser".into(),
+                })?;
+                let (value, validation_errors) = json_schema_parser.parse_payload(raw, *validate).await?;
+                Ok(ParsedPart {
+                    validation_errors,
+                    ..ParsedPart::new(value, RecordFormat::JsonSchema)
+                })
+            }
+            ParserMode::Json => Ok(ParsedPart::new(parse_json(raw)?, RecordFormat::Json)),
+            ParserMode::Hex => Ok(ParsedPart::new(parse_hex(raw), RecordFormat::Hex)),
+            ParserMode::Base64 => Ok(ParsedPart::new(parse_base64(raw), RecordFormat::Base64)),
+        }
+    }
+
+    async fn detect_mode(&self, raw: &[u8]) -> std::result::Result<ParserMode, String> {
+        match (&self.schema_registry_client, get_schema_id(raw)) {
+            (Some(client), Some(id)) => match client.get_raw_schema_by_id(id).await {
+                Ok(schema) => Ok(match schema.schema_type {
+                    SchemaType::Avro => ParserMode::Avro,
+                    SchemaType::Protobuf => ParserMode::Protobuf,
+                    SchemaType::Json => ParserMode::JsonSchema { validate: true },
+                }),
+                Err(err) => Err(format!(
+                    "Unable to retrieve the schema {} from schema registry\n{}",
+                    id,
+                    err.to_string()
+                )),
+            },
+            _ => Ok(ParserMode::String),
+        }
+    }
+}
+
+// registry framed records start with 0x00 followed by the schema id (4 bytes)
+fn get_schema_id(raw: &[u8]) -> Option<i32> {
+    if raw.len() >= 5 && raw[0] == 0x00 {
+        Some(i32::from_be_bytes([raw[1], raw[2], raw[3], raw[4]]))
+    } else {
+        None
+    }
 }
 
 #[async_trait]
@@ -43,7 +198,7 @@ impl<C> Parser for RecordParser<C>
 where
     C: SchemaRegistryClient + Sync + Send,
 {
-    async fn parse_record(&self, record: &RawKafkaRecord, mode: ParserMode) -> Result<ParsedKafkaRecord> {
+    async fn parse_record(&self, record: &RawKafkaRecord, settings: &TopicSettings) -> Result<ParsedKafkaRecord> {
         let RawKafkaRecord {
             payload,
             key,
@@ -51,29 +206,218 @@ where
             timestamp,
             partition,
             offset,
+            headers,
         } = record.clone();
-        let (key, payload) = match mode {
-            ParserMode::String => (key.map(|v| parse_string(&v)), payload.map(|v| parse_string(&v))),
-            ParserMode::Avro => {
-                let avro_parser = self.avro_parser.as_ref().ok_or(Error::AvroParse {
-                    message: "Missing avro parser".into(),
-                })?;
-                (
-                    key.map(|v| parse_string(&v)),
-                    match payload {
-                        Some(v) => Some(avro_parser.parse_payload(&v).await?),
-                        None => None,
-                    },
+        // key and payload can use different formats
+        let parsed_key = match &key {
+            Some(v) => Some(
+                self.parse_part(v, &settings.key_mode, settings.key_reader_schema.as_ref(), settings)
+                    .await,
+            ),
+            None => None,
+        };
+        let parsed_payload = match &payload {
+            Some(v) => Some(
+                self.parse_part(
+                    v,
+                    &settings.payload_mode,
+                    settings.payload_reader_schema.as_ref(),
+                    settings,
                 )
-            }
+                .await,
+            ),
+            None => None,
         };
+        let parse_errors: Vec<String> = [("key", &parsed_key), ("payload", &parsed_payload)]
+            .iter()
+            .filter_map(|(name, part)| {
+                part.as_ref()
+                    .and_then(|p| p.parse_error.as_ref())
+                    .map(|err| format!("Unable to parse the {}: {}", name, err))
+            })
+            .collect();
         Ok(ParsedKafkaRecord {
-            key,
-            payload,
+            key_format: parsed_key.as_ref().map(|k| k.format),
+            key_schema_id: parsed_key.as_ref().and_then(|k| k.schema_id),
+            key: parsed_key.map(|k| k.value),
+            payload_format: parsed_payload.as_ref().map(|p| p.format),
+            payload_schema_id: parsed_payload.as_ref().and_then(|p| p.schema_id),
+            validation_errors: parsed_payload.as_ref().and_then(|p| p.validation_errors.clone()),
+            payload: parsed_payload.map(|p| p.value),
+            raw_key: key,
+            raw_payload: payload,
             topic,
             timestamp,
             partition,
             offset,
+            headers,
+            projections: HashMap::new(),
+            parse_error: if parse_errors.is_empty() {
+                None
+            } else {
+                Some(parse_errors.join("\n"))
+            },
         })
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use apache_avro::{to_avro_datum, types::Value as AvroValue, Schema as ApacheAvroSchema};
+    use async_trait::async_trait;
+
+    use crate::lib::{
+        configuration::TopicSettings,
+        schema_registry::{RawSchema, Result, Schema, SchemaRegistryClient, SchemaRegistryError, SchemaType, Subject},
+        types::{RawKafkaRecord, RecordFormat},
+    };
+
+    use super::{Parser, ParserMode, RecordParser};
+
+    const KEY_SCHEMA: &str = r#"{"type": "record", "name": "Key", "fields": [{ "name": "id", "type": "long" }]}"#;
+
+    struct MockSchemaRegistry {}
+
+    #[async_trait]
+    impl SchemaRegistryClient for MockSchemaRegistry {
+        async fn list_subjects(&self) -> Result<Vec<String>> {
+            todo!()
+        }
+        async fn get_subject(&self, _: &str) -> Result<Subject> {
+            todo!()
+        }
+        async fn get_schema_by_id(&self, _: i32) -> Result<ApacheAvroSchema> {
+            Ok(ApacheAvroSchema::parse_str(KEY_SCHEMA).unwrap())
+        }
+        async fn get_raw_schema_by_id(&self, id: i32) -> Result<RawSchema> {
+            match id {
+                1 => Ok(RawSchema {
+                    schema: KEY_SCHEMA.into(),
+                    schema_type: SchemaType::Avro,
+                    references: vec![],
+                }),
+                _ => Err(SchemaRegistryError::HttpClient {
+                    message: "Schema not found".into(),
+                }),
+            }
+        }
+        async fn get_schema_version(&self, _: &str, _: Option<i32>) -> Result<Schema> {
+            todo!()
+        }
+        async fn delete_subject(&self, _: &str) -> Result<()> {
+            todo!()
+        }
+        async fn delete_version(&self, _: &str, _: i32) -> Result<()> {
+            todo!()
+        }
+    }
+
+    fn get_test_record(key: Vec<u8>, payload: Vec<u8>) -> RawKafkaRecord {
+        RawKafkaRecord {
+            payload: Some(payload),
+            key: Some(key),
+            topic: "topic".into(),
+            timestamp: None,
+            partition: 0,
+            offset: 0,
+            headers: vec![],
+        }
+    }
+
+    fn get_settings(key_mode: ParserMode, payload_mode: ParserMode) -> TopicSettings {
+        TopicSettings {
+            key_mode,
+            payload_mode,
+            ..Default::default()
+        }
+    }
+
+    #[tokio::test]
+    async fn test_auto_detect_key_and_payload_format() {
+        let schema = ApacheAvroSchema::parse_str(KEY_SCHEMA).unwrap();
+        let mut key = vec![0x00, 0x00, 0x00, 0x00, 0x01];
+        key.append(&mut to_avro_datum(&schema, AvroValue::Record(vec![("id".into(), AvroValue::Long(7))])).unwrap());
+        let sut = RecordParser::new(Some(Arc::new(MockSchemaRegistry {})));
+
+        let res = sut
+            .parse_record(
+                &get_test_record(key, "plain payload".into()),
+                &get_settings(ParserMode::Auto, ParserMode::Auto),
+            )
+            .await
+            .unwrap();
+
+        assert_eq!(res.key, Some(r#"{"id":7}"#.into()));
+        assert_eq!(res.key_format, Some(RecordFormat::Avro));
+        assert_eq!(res.key_schema_id, Some(1));
+        assert_eq!(res.payload, Some("plain payload".into()));
+        assert_eq!(res.payload_format, Some(RecordFormat::String));
+    }
+
+    #[tokio::test]
+    async fn test_auto_fallback_to_string() {
+        // framed like a registry record, but the schema doesn't exist
+        let payload = vec![0x00, 0x00, 0x00, 0x00, 0x02];
+        let sut = RecordParser::new(Some(Arc::new(MockSchemaRegistry {})));
+
+        let res = sut
+            .parse_record(
+                &get_test_record("key".into(), payload),
+                &get_settings(ParserMode::Auto, ParserMode::Auto),
+            )
+            .await
+            .unwrap();
+
+        assert_eq!(res.key_format, Some(RecordFormat::String));
+        assert_eq!(res.payload_format, Some(RecordFormat::String));
+        assert_eq!(res.payload_schema_id, None);
+        assert_eq!(
+            res.parse_error,
+            Some(
+                "Unable to parse the payload: Unable to retrieve the schema 2 from schema registry\nSchema not found"
+                    .into()
+            )
+        );
+    }
+
+    #[tokio::test]
+    async fn test_explicit_key_and_payload_mode() {
+        let sut = RecordParser::new(Some(Arc::new(MockSchemaRegistry {})));
+
+        let res = sut
+            .parse_record(
+                &get_test_record(vec![0xca, 0xfe], r#"{"a":1}"#.into()),
+                &get_settings(ParserMode::Hex, ParserMode::Json),
+            )
+            .await
+            .unwrap();
+
+        assert_eq!(res.key, Some("cafe".into()));
+        assert_eq!(res.key_format, Some(RecordFormat::Hex));
+        assert_eq!(res.payload, Some(r#"{"a":1}"#.into()));
+        assert_eq!(res.payload_format, Some(RecordFormat::Json));
+        assert_eq!(res.parse_error, None);
+    }
+
+    #[tokio::test]
+    async fn test_store_parse_error() {
+        let sut = RecordParser::new(Some(Arc::new(MockSchemaRegistry {})));
+
+        let res = sut
+            .parse_record(
+                &get_test_record("key".into(), "not avro".into()),
+                &get_settings(ParserMode::String, ParserMode::Avro),
+            )
+            .await
+            .unwrap();
+
+        assert_eq!(res.key_format, Some(RecordFormat::String));
+        assert_eq!(res.payload, Some("not avro".into()));
+        assert_eq!(res.payload_format, Some(RecordFormat::String));
+        assert_eq!(res.payload_schema_id, None);
+        assert_eq!(res.raw_payload, Some("not avro".into()));
+        assert!(res.parse_error.unwrap().starts_with("Unable to parse the payload"));
+    }
+}
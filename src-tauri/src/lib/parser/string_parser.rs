@@ -1,10 +1,26 @@
+use crate::lib::error::Result;
+
 pub(super) fn parse_string(v: &[u8]) -> String {
     String::from_utf8_lossy(v).into_owned()
 }
 
+// returns the original text if it's a valid json
+pub(super) fn parse_json(v: &[u8]) -> Result<String> {
+    let _: serde_json::Value = serde_json::from_slice(v)?;
+    Ok(parse_string(v))
+}
+
+pub(super) fn parse_hex(v: &[u8]) -> String {
+    v.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+pub(super) fn parse_base64(v: &[u8]) -> String {
+    base64::encode(v)
+}
+
 #[cfg(test)]
 mod tests {
-    use super::parse_string;
+    use super::{parse_base64, parse_hex, parse_json, parse_string};
 
     #[test]
     fn parse_empty_array_to_string() {
@@ -19,4 +35,17 @@ mod tests {
         let res = parse_string(&vec);
         assert!(!res.is_empty())
     }
+
+    #[test]
+    fn parse_json_payload() {
+        assert_eq!(parse_json(br#"{"a": [1, 2]}"#).unwrap(), r#"{"a": [1, 2]}"#);
+        assert!(parse_json(b"not a json").is_err());
+    }
+
+    #[test]
+    fn parse_binary_to_hex_and_base64() {
+        let vec: Vec<u8> = vec![0x00, 0x41, 0xff];
+        assert_eq!(parse_hex(&vec), "0041ff");
+        assert_eq!(parse_base64(&vec), "AEH/");
+    }
 }
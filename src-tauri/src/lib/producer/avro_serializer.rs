@@ -0,0 +1,617 @@
+use std::{collections::HashMap, str::FromStr, sync::Arc};
+
+use apache_avro::{
+    schema::{Name, UnionSchema},
+    to_avro_datum,
+    types::Value as AvroValue,
+    Days, Decimal as AvroDecimal, Duration, Millis, Months, Schema,
+};
+use num_bigint::{BigInt, Sign};
+use serde_json::Value as JsonValue;
+
+use crate::lib::{
+    error::{Error, Result},
+    parser::AvroEncoding,
+    producer::SchemaSelector,
+    schema_registry::{CachedSchemaRegistry, SchemaRegistryClient},
+};
+
+pub struct AvroSerializer<C = CachedSchemaRegistry>
+where
+    C: SchemaRegistryClient + Send + Sync,
+{
+    schema_registry_client: Arc<C>,
+}
+
+impl<C> AvroSerializer<C>
+where
+    C: SchemaRegistryClient + Send + Sync,
+{
+    pub fn new(schema_registry_client: Arc<C>) -> Self {
+        AvroSerializer { schema_registry_client }
+    }
+
+    // the json is expected in the given encoding, the friendly ones are handled the same way
+    pub async fn serialize(
+        &self,
+        json: &str,
+        schema_selector: &SchemaSelector,
+        encoding: &AvroEncoding,
+    ) -> Result<Vec<u8>> {
+        let (id, schema) = self.get_schema(schema_selector).await.map_err(|err| Error::AvroParse {
+            message: format!(
+                "{}\n{}",
+                "Unable to retrieve the schema from schema registry",
+                err.to_string()
+            ),
+        })?;
+        let json: JsonValue = serde_json::from_str(json)?;
+        let value = map(&json, &schema, encoding, &mut HashMap::new())?;
+        let mut encoded = to_avro_datum(&schema, value).map_err(|err| Error::AvroParse {
+            message: format!("{}\n{}", "Unable to encode the avro record", err),
+        })?;
+        // confluent wire format: 1 magic byte + 4 id bytes + avro datum
+        let mut raw = vec![0x00];
+        raw.extend_from_slice(&id.to_be_bytes());
+        raw.append(&mut encoded);
+        Ok(raw)
+    }
+
+    async fn get_schema(&self, schema_selector: &SchemaSelector) -> crate::lib::schema_registry::Result<(i32, Schema)> {
+        let id = match schema_selector {
+            SchemaSelector::Id(id) => *id,
+            SchemaSelector::Subject(subject) => self.schema_registry_client.get_schema_version(subject, None).await?.id,
+        };
+        Ok((id, self.schema_registry_client.get_schema_by_id(id).await?))
+    }
+}
+
+fn map<'a>(
+    value: &JsonValue,
+    schema: &'a Schema,
+    encoding: &AvroEncoding,
+    ref_cache: &mut HashMap<&'a Name, &'a Schema>, //cache to resolve avro references
+) -> Result<AvroValue> {
+    let avro_json = *encoding == AvroEncoding::AvroJson;
+    match (value, schema) {
+        (JsonValue::Null, Schema::Null) => Ok(AvroValue::Null),
+        (JsonValue::Bool(v), Schema::Boolean) => Ok(AvroValue::Boolean(*v)),
+        (JsonValue::Number(v), Schema::Int) => Ok(AvroValue::Int(to_i32(v)?)),
+        (JsonValue::Number(v), Schema::Long) => Ok(AvroValue::Long(to_i64(v)?)),
+        (JsonValue::Number(v), Schema::Float) => Ok(AvroValue::Float(to_f64(v)? as f32)),
+        (JsonValue::Number(v), Schema::Double) => Ok(AvroValue::Double(to_f64(v)?)),
+        (JsonValue::String(v), Schema::String) => Ok(AvroValue::String(v.clone())),
+        (v, Schema::Bytes) => Ok(AvroValue::Bytes(to_bytes(v)?)),
+        (JsonValue::Array(v), Schema::Array(s)) => {
+            let mut avro_vec = Vec::new();
+            for v in v.iter() {
+                avro_vec.push(map(v, s, encoding, ref_cache)?);
+            }
+            Ok(AvroValue::Array(avro_vec))
+        }
+        (JsonValue::Object(v), Schema::Map(s)) => {
+            let mut avro_map = HashMap::new();
+            for (k, v) in v.iter() {
+                avro_map.insert(k.clone(), map(v, s, encoding, ref_cache)?);
+            }
+            Ok(AvroValue::Map(avro_map))
+        }
+        (JsonValue::Object(v), Schema::Record { name, fields, .. }) => {
+            ref_cache.insert(name, schema);
+            let mut avro_fields = Vec::new();
+            for field in fields.iter() {
+                let value = match (v.get(&field.name), &field.default) {
+                    (Some(value), _) => value,
+                    (None, Some(default)) => default,
+                    (None, None) => {
+                        return Err(Error::AvroParse {
+                            message: format!("Missing field {} in record {}", field.name, name.name),
+                        })
+                    }
+                };
+                avro_fields.push((field.name.clone(), map(value, &field.schema, encoding, ref_cache)?));
+            }
+            Ok(AvroValue::Record(avro_fields))
+        }
+        (JsonValue::Number(v), Schema::Date) => Ok(AvroValue::Date(to_i32(v)?)),
+        (JsonValue::Number(v), Schema::TimeMillis) => Ok(AvroValue::TimeMillis(to_i32(v)?)),
+        (JsonValue::Number(v), Schema::TimeMicros) => Ok(AvroValue::TimeMicros(to_i64(v)?)),
+        (JsonValue::Number(v), Schema::TimestampMillis) => Ok(AvroValue::TimestampMillis(to_i64(v)?)),
+        (JsonValue::Number(v), Schema::TimestampMicros) => Ok(AvroValue::TimestampMicros(to_i64(v)?)),
+        (JsonValue::String(v), Schema::Uuid) => {
+            AvroValue::String(v.clone())
+                .resolve(schema)
+                .map_err(|err| Error::AvroParse {
+                    message: format!("Invalid uuid {}. {}", v, err),
+                })
+        }
+        // avro-json format: the two's complement bytes of the unscaled value, even if they look like a number
+        (v @ JsonValue::String(_), Schema::Decimal { .. }) if avro_json => {
+            Ok(AvroValue::Decimal(AvroDecimal::from(to_bytes(v)?)))
+        }
+        (
+            v @ (JsonValue::String(_) | JsonValue::Number(_)),
+            Schema::Decimal {
+                precision,
+                scale,
+                inner,
+            },
+        ) => {
+            let raw = match v {
+                JsonValue::String(s) => s.clone(),
+                _ => v.to_string(),
+            };
+            let unscaled = to_unscaled_decimal(&raw, *precision, *scale)?;
+            let mut bytes = unscaled.to_signed_bytes_be();
+            // the fixed decimals use all the bytes of the fixed, sign-extended
+            if let Schema::Fixed { size, .. } = **inner {
+                if bytes.len() > size {
+                    return Err(Error::AvroParse {
+                        message: format!("Invalid decimal {}. It doesn't fit in {} bytes", raw, size),
+                    });
+                }
+                let sign = if unscaled.sign() == Sign::Minus { 0xff } else { 0x00 };
+                let mut extended = vec![sign; size - bytes.len()];
+                extended.append(&mut bytes);
+                bytes = extended;
+            }
+            Ok(AvroValue::Decimal(AvroDecimal::from(bytes)))
+        }
+        (JsonValue::Object(v), Schema::Duration) => {
+            let get = |field: &str| {
+                v.get(field)
+                    .and_then(|f| f.as_u64())
+                    .and_then(|f| u32::try_from(f).ok())
+                    .ok_or(Error::AvroParse {
+                        message: format!("Invalid duration. Missing or invalid field {}", field),
+                    })
+            };
+            Ok(AvroValue::Duration(Duration::new(
+                Months::new(get("months")?),
+                Days::new(get("days")?),
+                Millis::new(get("millis")?),
+            )))
+        }
+        (v @ JsonValue::String(_), Schema::Duration) if avro_json => {
+            // avro-json format: fixed of 12 bytes with months, days and millis as little-endian u32
+            let bytes = to_bytes(v)?;
+            let get = |i: usize| {
+                bytes
+                    .get(i * 4..(i + 1) * 4)
+                    .and_then(|b| <[u8; 4]>::try_from(b).ok())
+                    .map(u32::from_le_bytes)
+                    .ok_or(Error::AvroParse {
+                        message: "Invalid duration. Expected 12 bytes".into(),
+                    })
+            };
+            Ok(AvroValue::Duration(Duration::new(
+                Months::new(get(0)?),
+                Days::new(get(1)?),
+                Millis::new(get(2)?),
+            )))
+        }
+        (value, Schema::Union(s)) => map_union(value, s, encoding, ref_cache),
+        (JsonValue::String(v), Schema::Enum { name, symbols, .. }) => {
+            ref_cache.insert(name, schema);
+            let index = symbols.iter().position(|s| s == v).ok_or(Error::AvroParse {
+                message: format!("Invalid symbol {} for enum {}", v, name.name),
+            })?;
+            Ok(AvroValue::Enum(index as u32, v.clone()))
+        }
+        (v, Schema::Fixed { name, size, .. }) => {
+            ref_cache.insert(name, schema);
+            let bytes = to_bytes(v)?;
+            if bytes.len() != *size {
+                return Err(Error::AvroParse {
+                    message: format!("Invalid size for the fixed {}. Expected {} bytes", name.name, size),
+                });
+            }
+            Ok(AvroValue::Fixed(*size, bytes))
+        }
+        (value, Schema::Ref { name }) => {
+            let schema = *ref_cache.get(name).ok_or(Error::AvroParse {
+                message: format!("Missing Avro schema reference {:?}", name),
+            })?;
+            map(value, schema, encoding, ref_cache)
+        }
+        (v, s) => Err(Error::AvroParse {
+            message: format!("Unable to map the json value {} to the avro schema {:?}", v, s),
+        }),
+    }
+}
+
+fn map_union<'a>(
+    value: &JsonValue,
+    schema: &'a UnionSchema,
+    encoding: &AvroEncoding,
+    ref_cache: &mut HashMap<&'a Name, &'a Schema>,
+) -> Result<AvroValue> {
+    for s in schema.variants() {
+        if let Schema::Record { name, .. } | Schema::Enum { name, .. } | Schema::Fixed { name, .. } = s {
+            ref_cache.insert(name, s);
+        }
+    }
+    // avro-json format: the union value is wrapped in an object with the type name as key
+    if let JsonValue::Object(obj) = value {
+        if let Some((type_name, inner)) = obj.iter().next().filter(|_| obj.len() == 1) {
+            let branch = schema
+                .variants()
+                .iter()
+                .position(|s| get_type_names(s).iter().any(|n| n == type_name));
+            if let Some(i) = branch {
+                let avro_value = map(inner, &schema.variants()[i], encoding, ref_cache)?;
+                return Ok(AvroValue::Union(i as u32, Box::new(avro_value)));
+            }
+        }
+    }
+    // otherwise pick the first branch that matches the value
+    for (i, s) in schema.variants().iter().enumerate() {
+        if let Ok(avro_value) = map(value, s, encoding, ref_cache) {
+            return Ok(AvroValue::Union(i as u32, Box::new(avro_value)));
+        }
+    }
+    Err(Error::AvroParse {
+        message: format!("The json value {} doesn't match any type in the union", value),
+    })
+}
+
+fn get_type_names(schema: &Schema) -> Vec<String> {
+    match schema {
+        Schema::Record { name, .. } | Schema::Enum { name, .. } | Schema::Fixed { name, .. } => {
+            let mut names = vec![name.name.clone()];
+            if let Some(namespace) = &name.namespace {
+                names.push(format!("{}.{}", namespace, name.name));
+            }
+            names
+        }
+        Schema::Null => vec!["null".into()],
+        Schema::Boolean => vec!["boolean".into()],
+        Schema::Int | Schema::Date | Schema::TimeMillis => vec!["int".into()],
+        Schema::Long | Schema::TimeMicros | Schema::TimestampMillis | Schema::TimestampMicros => vec!["long".into()],
+        Schema::Float => vec!["float".into()],
+        Schema::Double => vec!["double".into()],
+        Schema::Bytes | Schema::Decimal { .. } => vec!["bytes".into()],
+        Schema::String | Schema::Uuid => vec!["string".into()],
+        Schema::Array(_) => vec!["array".into()],
+        Schema::Map(_) => vec!["map".into()],
+        // the branch is named after the underlying fixed, which is not available
+        Schema::Duration => vec![],
+        Schema::Ref { name } => vec![name.name.clone()],
+        _ => vec![],
+    }
+}
+
+fn to_i32(v: &serde_json::Number) -> Result<i32> {
+    to_i64(v).and_then(|v| {
+        i32::try_from(v).map_err(|_| Error::AvroParse {
+            message: format!("The value {} doesn't fit in an avro int", v),
+        })
+    })
+}
+
+fn to_i64(v: &serde_json::Number) -> Result<i64> {
+    v.as_i64().ok_or(Error::AvroParse {
+        message: format!("The value {} is not a valid avro long", v),
+    })
+}
+
+fn to_f64(v: &serde_json::Number) -> Result<f64> {
+    v.as_f64().ok_or(Error::AvroParse {
+        message: format!("The value {} is not a valid avro double", v),
+    })
+}
+
+// unscaled value of a decimal in plain or scientific notation. The value is not rounded,
+// it's an error if it has more digits than the precision or the scale of the schema
+fn to_unscaled_decimal(raw: &str, precision: usize, scale: usize) -> Result<BigInt> {
+    let invalid = |reason: &str| Error::AvroParse {
+        message: format!("Invalid decimal {}. {}", raw, reason),
+    };
+    let (mantissa, exponent) = match raw.find(['e', 'E']) {
+        Some(index) => (
+            &raw[..index],
+            raw[index + 1..]
+                .parse::<i64>()
+                .map_err(|_| invalid("Invalid exponent"))?,
+        ),
+        None => (raw, 0),
+    };
+    let (negative, mantissa) = match mantissa.strip_prefix('-') {
+        Some(mantissa) => (true, mantissa),
+        None => (false, mantissa.strip_prefix('+').unwrap_or(mantissa)),
+    };
+    let (integer, fraction) = mantissa.split_once('.').unwrap_or((mantissa, ""));
+    let digits = format!("{}{}", integer, fraction);
+    if digits.is_empty() || !digits.chars().all(|c| c.is_ascii_digit()) {
+        return Err(invalid("Not a number"));
+    }
+    let mut unscaled = BigInt::from_str(&digits).map_err(|err| invalid(&err.to_string()))?;
+    if unscaled.sign() == Sign::NoSign {
+        return Ok(unscaled);
+    }
+    if negative {
+        unscaled = -unscaled;
+    }
+    // digits to add (or remove) to get the scale of the schema
+    let shift = scale as i64 - (fraction.len() as i64).saturating_sub(exponent);
+    if shift > precision as i64 {
+        return Err(invalid(&format!("It exceeds the precision {}", precision)));
+    } else if shift > 0 {
+        unscaled *= BigInt::from(10).pow(shift as u32);
+    } else if shift < 0 {
+        let divisor = BigInt::from(10).pow((-shift).min(digits.len() as i64 + 1) as u32);
+        if (&unscaled % &divisor).sign() != Sign::NoSign {
+            return Err(invalid(&format!("It exceeds the scale {}", scale)));
+        }
+        unscaled /= divisor;
+    }
+    if unscaled.magnitude().to_string().len() > precision {
+        return Err(invalid(&format!("It exceeds the precision {}", precision)));
+    }
+    Ok(unscaled)
+}
+
+// bytes can be either a string in which each char is a byte (avro-json format)
+// or an array of integers
+fn to_bytes(v: &JsonValue) -> Result<Vec<u8>> {
+    let invalid = || Error::AvroParse {
+        message: format!("Unable to convert {} to avro bytes", v),
+    };
+    match v {
+        JsonValue::String(s) => s.chars().map(|c| u8::try_from(c).map_err(|_| invalid())).collect(),
+        JsonValue::Array(arr) => arr
+            .iter()
+            .map(|b| b.as_u64().and_then(|b| u8::try_from(b).ok()).ok_or_else(invalid))
+            .collect(),
+        _ => Err(invalid()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{collections::HashMap, sync::Arc};
+
+    use apache_avro::{from_avro_datum, to_avro_datum, types::Value as AvroValue, Schema as ApacheAvroSchema};
+    use async_trait::async_trait;
+
+    use crate::lib::{
+        parser::{AvroEncoding, AvroParser},
+        producer::SchemaSelector,
+        schema_registry::{RawSchema, Result, Schema, SchemaRegistryClient, SchemaType, Subject},
+    };
+
+    use super::{map, to_unscaled_decimal, AvroSerializer};
+
+    struct MockSchemaRegistry {
+        schema: String,
+    }
+
+    #[async_trait]
+    impl SchemaRegistryClient for MockSchemaRegistry {
+        async fn list_subjects(&self) -> Result<Vec<String>> {
+            todo!()
+        }
+        async fn get_subject(&self, _: &str) -> Result<Subject> {
+            todo!()
+        }
+        async fn get_schema_by_id(&self, _: i32) -> Result<ApacheAvroSchema> {
+            Ok(ApacheAvroSchema::parse_str(&self.schema).unwrap())
+        }
+        async fn get_raw_schema_by_id(&self, _: i32) -> Result<RawSchema> {
+            todo!()
+        }
+        async fn get_schema_version(&self, _: &str, _: Option<i32>) -> Result<Schema> {
+            Ok(Schema {
+                id: 100037,
+                version: 1,
+                schema: self.schema.clone(),
+                schema_type: SchemaType::Avro,
+                references: vec![],
+            })
+        }
+        async fn delete_subject(&self, _: &str) -> Result<()> {
+            todo!()
+        }
+        async fn delete_version(&self, _: &str, _: i32) -> Result<()> {
+            todo!()
+        }
+    }
+
+    const RAW_SCHEMA: &str = r#"
+    {
+        "fields": [
+            { "name": "int_field", "type": "int" },
+            { "name": "string_field", "type": "string" },
+            { "name": "optional_field", "type": ["null", "string"] },
+            { "name": "default_field", "type": "long", "default": 10 },
+            { "name": "bytes_field", "type": "bytes" },
+            { "name": "enum_field", "type": { "type": "enum", "name": "sampleEnum", "symbols": ["A", "B"] } }
+        ],
+        "name": "sampleRecord",
+        "namespace": "com.example.namespace",
+        "type": "record"
+    }"#;
+
+    fn get_sut() -> AvroSerializer<MockSchemaRegistry> {
+        AvroSerializer::new(Arc::new(MockSchemaRegistry {
+            schema: RAW_SCHEMA.into(),
+        }))
+    }
+
+    fn decode(raw: &[u8]) -> AvroValue {
+        let schema = ApacheAvroSchema::parse_str(RAW_SCHEMA).unwrap();
+        from_avro_datum(&schema, &mut &raw[5..], None).unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_serialize_with_subject() {
+        let json = r#"{"int_field": 12, "string_field": "YO!! test", "optional_field": {"string": "opt"}, "bytes_field": [1, 2, 170], "enum_field": "B"}"#;
+
+        let res = get_sut()
+            .serialize(
+                json,
+                &SchemaSelector::Subject("sample-value".into()),
+                &AvroEncoding::Friendly,
+            )
+            .await
+            .unwrap();
+
+        // magic byte and schema id
+        assert_eq!(res[..5], [0x00, 0x00, 0x01, 0x86, 0xc5]);
+        assert_eq!(
+            decode(&res),
+            AvroValue::Record(vec![
+                ("int_field".into(), AvroValue::Int(12)),
+                ("string_field".into(), AvroValue::String("YO!! test".into())),
+                (
+                    "optional_field".into(),
+                    AvroValue::Union(1, Box::new(AvroValue::String("opt".into())))
+                ),
+                ("default_field".into(), AvroValue::Long(10)),
+                ("bytes_field".into(), AvroValue::Bytes(vec![0x01, 0x02, 0xaa])),
+                ("enum_field".into(), AvroValue::Enum(1, "B".into())),
+            ])
+        )
+    }
+
+    #[tokio::test]
+    async fn test_serialize_with_schema_id() {
+        let json =
+            r#"{"int_field": 1, "string_field": "", "optional_field": null, "bytes_field": "ÿ", "enum_field": "A"}"#;
+
+        let res = get_sut()
+            .serialize(json, &SchemaSelector::Id(123), &AvroEncoding::Friendly)
+            .await
+            .unwrap();
+
+        assert_eq!(res[..5], [0x00, 0x00, 0x00, 0x00, 0x7b]);
+        assert_eq!(
+            decode(&res),
+            AvroValue::Record(vec![
+                ("int_field".into(), AvroValue::Int(1)),
+                ("string_field".into(), AvroValue::String("".into())),
+                ("optional_field".into(), AvroValue::Union(0, Box::new(AvroValue::Null))),
+                ("default_field".into(), AvroValue::Long(10)),
+                ("bytes_field".into(), AvroValue::Bytes(vec![0xff])),
+                ("enum_field".into(), AvroValue::Enum(0, "A".into())),
+            ])
+        )
+    }
+
+    #[tokio::test]
+    async fn test_serialize_invalid_json() {
+        let sut = get_sut();
+        let missing_field = r#"{"int_field": 1}"#;
+        let invalid_enum =
+            r#"{"int_field": 1, "string_field": "", "optional_field": null, "bytes_field": "", "enum_field": "C"}"#;
+        let int_overflow = r#"{"int_field": 12345678901, "string_field": "", "optional_field": null, "bytes_field": "", "enum_field": "A"}"#;
+
+        for json in [missing_field, invalid_enum, int_overflow, "not a json"] {
+            let res = sut
+                .serialize(json, &SchemaSelector::Id(1), &AvroEncoding::Friendly)
+                .await;
+            assert!(res.is_err(), "{} should fail", json);
+        }
+    }
+
+    #[tokio::test]
+    async fn test_avro_json_round_trip() {
+        let raw_schema = r#"
+    {
+        "fields": [
+            { "name": "decimal_field", "type": { "type": "bytes", "logicalType": "decimal", "precision": 5, "scale": 2 } },
+            { "name": "decimal_union", "type": ["null", { "type": "bytes", "logicalType": "decimal", "precision": 5, "scale": 2 }] },
+            { "name": "bytes_field", "type": "bytes" }
+        ],
+        "name": "sampleRecord",
+        "type": "record"
+    }"#;
+        let schema_registry = Arc::new(MockSchemaRegistry {
+            schema: raw_schema.into(),
+        });
+        // the unscaled value 12345 (123.45) is encoded as the bytes of the ascii digits "09"
+        let record = AvroValue::Record(vec![
+            ("decimal_field".into(), AvroValue::Decimal(vec![0x30, 0x39].into())),
+            (
+                "decimal_union".into(),
+                AvroValue::Union(1, Box::new(AvroValue::Decimal(vec![0x30, 0x39].into()))),
+            ),
+            ("bytes_field".into(), AvroValue::Bytes(vec![0x31, 0x32])),
+        ]);
+        let mut raw = vec![0x00, 0x00, 0x00, 0x00, 0x01];
+        raw.append(&mut to_avro_datum(&ApacheAvroSchema::parse_str(raw_schema).unwrap(), record).unwrap());
+
+        let json = AvroParser::new(schema_registry.clone())
+            .parse_payload(&raw, &AvroEncoding::AvroJson, None)
+            .await
+            .unwrap();
+        let res = AvroSerializer::new(schema_registry)
+            .serialize(&json, &SchemaSelector::Id(1), &AvroEncoding::AvroJson)
+            .await
+            .unwrap();
+
+        assert_eq!(
+            json,
+            r#"{"bytes_field":"12","decimal_field":"09","decimal_union":{"bytes":"09"}}"#
+        );
+        assert_eq!(res, raw);
+    }
+
+    #[test]
+    fn test_decimal_is_not_rounded() {
+        let unscaled = |raw| to_unscaled_decimal(raw, 40, 2).map(|v| v.to_string());
+        assert_eq!(unscaled("123.45").unwrap(), "12345");
+        assert_eq!(unscaled("-1.5").unwrap(), "-150");
+        assert_eq!(unscaled("1.500").unwrap(), "150");
+        assert_eq!(unscaled("1.2345e2").unwrap(), "12345");
+        assert_eq!(unscaled("0.000").unwrap(), "0");
+        // more digits than a 96 bits decimal
+        assert_eq!(
+            unscaled("12345678901234567890123456789012.34").unwrap(),
+            "1234567890123456789012345678901234"
+        );
+        // over the scale
+        assert!(unscaled("1.234").is_err());
+        assert!(unscaled("1e-3").is_err());
+        // over the precision
+        assert!(to_unscaled_decimal("1234.5", 5, 2).is_err());
+        assert!(to_unscaled_decimal("1e10", 5, 0).is_err());
+        for invalid in ["", "-", "1.2.3", "1_000", "abc", "1e", "1e-9223372036854775808"] {
+            assert!(unscaled(invalid).is_err(), "{} should fail", invalid);
+        }
+    }
+
+    #[test]
+    fn test_fixed_decimal_is_sign_extended() {
+        let schema = ApacheAvroSchema::parse_str(
+            r#"{"type": {"type": "fixed", "name": "amount", "size": 4}, "logicalType": "decimal", "precision": 9, "scale": 2}"#,
+        )
+        .unwrap();
+        let small_schema = ApacheAvroSchema::parse_str(
+            r#"{"type": {"type": "fixed", "name": "amount", "size": 2}, "logicalType": "decimal", "precision": 9, "scale": 2}"#,
+        )
+        .unwrap();
+        let map_decimal = |raw: &str, schema| {
+            map(
+                &serde_json::json!(raw),
+                schema,
+                &AvroEncoding::Friendly,
+                &mut HashMap::new(),
+            )
+        };
+
+        let negative = map_decimal("-1.23", &schema).unwrap();
+        let positive = map_decimal("1.28", &schema).unwrap();
+
+        // the decimal is encoded as the 4 bytes of the fixed
+        match &negative {
+            AvroValue::Decimal(decimal) => {
+                assert_eq!(<Vec<u8>>::try_from(decimal).unwrap(), vec![0xff, 0xff, 0xff, 0x85])
+            }
+            _ => panic!("the value must be a decimal"),
+        }
+        assert_eq!(to_avro_datum(&schema, negative).unwrap(), vec![0xff, 0xff, 0xff, 0x85]);
+        assert_eq!(to_avro_datum(&schema, positive).unwrap(), vec![0x00, 0x00, 0x00, 0x80]);
+        assert!(map_decimal("1000.00", &small_schema).is_err());
+    }
+}
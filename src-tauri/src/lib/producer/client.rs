@@ -0,0 +1,123 @@
+use std::{sync::Arc, time::Duration};
+
+use async_trait::async_trait;
+use log::{debug, trace};
+use rdkafka::{
+    message::{Header, OwnedHeaders},
+    producer::{FutureProducer, FutureRecord},
+};
+
+use crate::lib::{
+    configuration::{build_kafka_client_config, ClusterConfig},
+    error::{Error, Result},
+    parser::AvroEncoding,
+    producer::{ProducerMode, ProducerRecord},
+    schema_registry::{CachedSchemaRegistry, SchemaRegistryClient},
+};
+
+use super::avro_serializer::AvroSerializer;
+
+#[async_trait]
+pub trait Producer {
+    async fn produce_record(&self, record: &ProducerRecord, mode: &ProducerMode) -> Result<()>;
+}
+
+pub struct KafkaProducer<C = CachedSchemaRegistry>
+where
+    C: SchemaRegistryClient + Send + Sync,
+{
+    producer: FutureProducer,
+    timeout: Duration,
+    avro_serializer: Option<AvroSerializer<C>>,
+}
+
+impl<C> KafkaProducer<C>
+where
+    C: SchemaRegistryClient + Send + Sync,
+{
+    pub fn new(cluster_config: &ClusterConfig, schema_registry_client: Option<Arc<C>>) -> Self {
+        KafkaProducer {
+            producer: build_kafka_client_config(cluster_config, None)
+                .create()
+                .expect("Unable to create the kafka producer"),
+            timeout: Duration::from_secs(10),
+            avro_serializer: schema_registry_client.map(AvroSerializer::new),
+        }
+    }
+
+    async fn serialize_payload(&self, payload: &str, mode: &ProducerMode) -> Result<Vec<u8>> {
+        match mode {
+            ProducerMode::String => Ok(payload.as_bytes().to_vec()),
+            ProducerMode::Avro(schema_selector) | ProducerMode::AvroJson(schema_selector) => {
+                let avro_serializer = self.avro_serializer.as_ref().ok_or(Error::AvroParse {
+                    message: "Missing avro serializer".into(),
+                })?;
+                let encoding = match mode {
+                    ProducerMode::AvroJson(_) => AvroEncoding::AvroJson,
+                    _ => AvroEncoding::Friendly,
+                };
+                avro_serializer.serialize(payload, schema_selector, &encoding).await
+            }
+        }
+    }
+}
+
+#[async_trait]
+impl<C> Producer for KafkaProducer<C>
+where
+    C: SchemaRegistryClient + Send + Sync,
+{
+    async fn produce_record(&self, record: &ProducerRecord, mode: &ProducerMode) -> Result<()> {
+        let ProducerRecord {
+            topic,
+            key,
+            payload,
+            headers,
+            partition,
+            timestamp,
+        } = record;
+        debug!("Produce a new record to {}", topic);
+        let payload = match payload {
+            Some(payload) => Some(self.serialize_payload(payload, mode).await?),
+            None => None,
+        };
+        let mut future_record = FutureRecord::<str, [u8]>::to(topic);
+        if let Some(key) = key {
+            future_record = future_record.key(key.as_str());
+        }
+        if let Some(payload) = &payload {
+            future_record = future_record.payload(payload.as_slice());
+        }
+        if !headers.is_empty() {
+            let mut owned_headers = OwnedHeaders::new();
+            for header in headers {
+                let value = match &header.value {
+                    // the binary values are base64 encoded
+                    Some(value) if header.binary => Some(base64::decode(value).map_err(|err| Error::Kafka {
+                        message: format!("Invalid base64 value of the header {}\n{}", header.key, err),
+                    })?),
+                    Some(value) => Some(value.as_bytes().to_vec()),
+                    None => None,
+                };
+                owned_headers = owned_headers.insert(Header {
+                    key: &header.key,
+                    value: value.as_deref(),
+                });
+            }
+            future_record = future_record.headers(owned_headers);
+        }
+        if let Some(partition) = partition {
+            future_record = future_record.partition(*partition);
+        }
+        if let Some(timestamp) = timestamp {
+            future_record = future_record.timestamp(*timestamp);
+        }
+        let (partition, offset) = self
+            .producer
+            .send(future_record, self.timeout)
+            .await
+            .map_err(|(err, _)| err)?;
+        trace!("Record produced to partition {} offset {}", partition, offset);
+        Ok(())
+    }
+}
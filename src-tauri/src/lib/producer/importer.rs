@@ -0,0 +1,335 @@
+use std::{collections::HashMap, fs, path::Path};
+
+use log::debug;
+use serde::{Deserialize, Serialize};
+use serde_json::Value as JsonValue;
+
+use crate::lib::{
+    error::{Error, Result},
+    producer::{Producer, ProducerMode, ProducerRecord},
+    types::RecordHeader,
+};
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub enum ImportFormat {
+    Jsonl,
+    // the first row is the header, the supported columns are key, payload, headers, partition and timestamp
+    Csv,
+}
+
+#[derive(Serialize, Debug, Clone, PartialEq, Eq)]
+pub struct ImportFailure {
+    pub line: usize,
+    pub message: String,
+}
+
+#[derive(Serialize, Debug, Clone, PartialEq, Eq)]
+pub struct ImportResult {
+    #[serde(rename = "recordCount")]
+    pub record_count: usize, //records produced successfully
+    pub failures: Vec<ImportFailure>,
+}
+
+// a record read from the import file
+#[derive(Deserialize, Debug, Default, PartialEq, Eq)]
+struct ImportRecord {
+    key: Option<String>,
+    // avro payloads can be plain json objects
+    #[serde(default, deserialize_with = "deserialize_payload")]
+    payload: Option<String>,
+    // same format of the exported records, an array of {key, value} objects
+    #[serde(default)]
+    headers: Vec<RecordHeader>,
+    partition: Option<i32>,
+    timestamp: Option<i64>,
+}
+
+// produce the records in the file to the topic, a record that can't be parsed
+// or produced is reported in the result without stopping the import
+pub async fn import_records<P: Producer + Send + Sync>(
+    producer: &P,
+    topic: &str,
+    file_path: &Path,
+    format: &ImportFormat,
+    mode: &ProducerMode,
+) -> Result<ImportResult> {
+    let content = fs::read_to_string(file_path)?;
+    let records = match format {
+        ImportFormat::Jsonl => parse_jsonl(&content),
+        ImportFormat::Csv => parse_csv(&content)?,
+    };
+    let mut result = ImportResult {
+        record_count: 0,
+        failures: vec![],
+    };
+    for (line, record) in records {
+        let produced = match record {
+            Ok(record) => {
+                producer
+                    .produce_record(
+                        &ProducerRecord {
+                            topic: topic.into(),
+                            key: record.key,
+                            payload: record.payload,
+                            headers: record.headers,
+                            partition: record.partition,
+                            timestamp: record.timestamp,
+                        },
+                        mode,
+                    )
+                    .await
+            }
+            Err(err) => Err(err),
+        };
+        match produced {
+            Ok(_) => result.record_count += 1,
+            Err(err) => result.failures.push(ImportFailure {
+                line,
+                message: err.to_string(),
+            }),
+        }
+    }
+    debug!(
+        "Imported {} records to {} with {} failures",
+        result.record_count,
+        topic,
+        result.failures.len()
+    );
+    Ok(result)
+}
+
+fn parse_jsonl(content: &str) -> Vec<(usize, Result<ImportRecord>)> {
+    content
+        .lines()
+        .enumerate()
+        .filter(|(_, line)| !line.trim().is_empty())
+        .map(|(index, line)| (index + 1, serde_json::from_str(line).map_err(Error::from)))
+        .collect()
+}
+
+fn parse_csv(content: &str) -> Result<Vec<(usize, Result<ImportRecord>)>> {
+    let mut rows = split_csv_rows(content).into_iter();
+    let (_, header) = rows.next().ok_or_else(|| Error::IO {
+        message: "The csv file must start with a header".into(),
+    })?;
+    let columns: HashMap<String, usize> = header
+        .into_iter()
+        .enumerate()
+        .map(|(index, name)| (name.trim().to_lowercase(), index))
+        .collect();
+    if !columns.contains_key("key") && !columns.contains_key("payload") {
+        return Err(Error::IO {
+            message: "The csv header must contain at least one of the key or payload columns".into(),
+        });
+    }
+    Ok(rows.map(|(line, row)| (line, parse_csv_row(&columns, &row))).collect())
+}
+
+fn parse_csv_row(columns: &HashMap<String, usize>, row: &[String]) -> Result<ImportRecord> {
+    // empty fields are treated as missing values
+    let get = |name: &str| {
+        columns
+            .get(name)
+            .and_then(|index| row.get(*index))
+            .filter(|value| !value.is_empty())
+    };
+    Ok(ImportRecord {
+        key: get("key").cloned(),
+        payload: get("payload").cloned(),
+        headers: get("headers")
+            .map(|headers| serde_json::from_str(headers))
+            .transpose()?
+            .unwrap_or_default(),
+        partition: get("partition").map(|p| parse_number(p, "partition")).transpose()?,
+        timestamp: get("timestamp").map(|t| parse_number(t, "timestamp")).transpose()?,
+    })
+}
+
+fn parse_number<T: std::str::FromStr>(value: &str, column: &str) -> Result<T> {
+    value.trim().parse().map_err(|_| Error::IO {
+        message: format!("Invalid {} value: {}", column, value),
+    })
+}
+
+// split the csv content in rows of fields, quoted fields can contain commas,
+// escaped quotes ("") and new lines. Each row is returned with its first line number
+fn split_csv_rows(content: &str) -> Vec<(usize, Vec<String>)> {
+    let mut rows = vec![];
+    let (mut row, mut field) = (vec![], String::new());
+    let (mut line, mut row_line) = (1, 1);
+    let mut in_quotes = false;
+    let mut chars = content.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            '"' if in_quotes && chars.peek() == Some(&'"') => {
+                chars.next();
+                field.push('"');
+            }
+            '"' => in_quotes = !in_quotes,
+            ',' if !in_quotes => row.push(std::mem::take(&mut field)),
+            '\r' if !in_quotes => {}
+            '\n' if !in_quotes => {
+                row.push(std::mem::take(&mut field));
+                let completed = std::mem::take(&mut row);
+                // skip the empty lines
+                if completed.len() > 1 || !completed[0].is_empty() {
+                    rows.push((row_line, completed));
+                }
+                line += 1;
+                row_line = line;
+            }
+            c => {
+                if c == '\n' {
+                    line += 1;
+                }
+                field.push(c);
+            }
+        }
+    }
+    if !row.is_empty() || !field.is_empty() {
+        row.push(field);
+        rows.push((row_line, row));
+    }
+    rows
+}
+
+fn deserialize_payload<'de, D>(deserializer: D) -> std::result::Result<Option<String>, D::Error>
+where
+    D: serde::Deserializer<'de>,
+{
+    Ok(match Option::<JsonValue>::deserialize(deserializer)? {
+        None | Some(JsonValue::Null) => None,
+        Some(JsonValue::String(payload)) => Some(payload),
+        Some(json) => Some(json.to_string()),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Write;
+
+    use async_trait::async_trait;
+
+    use crate::lib::{
+        error::{Error, Result},
+        producer::{Producer, ProducerMode, ProducerRecord},
+        types::RecordHeader,
+    };
+
+    use super::{import_records, parse_csv, parse_jsonl, split_csv_rows, ImportFormat, ImportRecord};
+
+    struct MockProducer {}
+
+    #[async_trait]
+    impl Producer for MockProducer {
+        async fn produce_record(&self, record: &ProducerRecord, _: &ProducerMode) -> Result<()> {
+            match record.key.as_deref() {
+                Some("fail") => Err(Error::Kafka {
+                    message: "Unable to produce".into(),
+                }),
+                _ => Ok(()),
+            }
+        }
+    }
+
+    #[test]
+    fn test_parse_jsonl() {
+        let content = r#"{"key":"k1","payload":"p1","headers":[{"key":"h","value":"v"},{"key":"h","value":null}],"partition":2}
+
+{"key":"k2","payload":{"name":"bob"},"offset":12}
+not a json"#;
+        let records = parse_jsonl(content);
+        assert_eq!(records.len(), 3);
+        assert_eq!(
+            records[0].1.as_ref().unwrap(),
+            &ImportRecord {
+                key: Some("k1".into()),
+                payload: Some("p1".into()),
+                headers: vec![
+                    RecordHeader {
+                        key: "h".into(),
+                        value: Some("v".into()),
+                        binary: false,
+                    },
+                    RecordHeader {
+                        key: "h".into(),
+                        value: None,
+                        binary: false,
+                    },
+                ],
+                partition: Some(2),
+                timestamp: None,
+            }
+        );
+        assert_eq!(records[1].0, 3);
+        assert_eq!(records[1].1.as_ref().unwrap().payload, Some(r#"{"name":"bob"}"#.into()));
+        assert_eq!(records[2].0, 4);
+        assert!(records[2].1.is_err());
+    }
+
+    #[test]
+    fn test_split_csv_rows() {
+        let content = "a,b\r\n\"x,1\",\"multi\nline \"\"quoted\"\"\"\n\nlast,";
+        assert_eq!(
+            split_csv_rows(content),
+            vec![
+                (1, vec!["a".to_string(), "b".into()]),
+                (2, vec!["x,1".into(), "multi\nline \"quoted\"".into()]),
+                (5, vec!["last".into(), "".into()]),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_parse_csv() {
+        let content =
+            "partition,key,payload,headers\n1,k1,p1,\"[{\"\"key\"\":\"\"h\"\",\"\"value\"\":\"\"v\"\"}]\"\n,,p2,\nx,k3,p3,";
+        let records = parse_csv(content).unwrap();
+        assert_eq!(
+            records[0].1.as_ref().unwrap(),
+            &ImportRecord {
+                key: Some("k1".into()),
+                payload: Some("p1".into()),
+                headers: vec![RecordHeader {
+                    key: "h".into(),
+                    value: Some("v".into()),
+                    binary: false,
+                }],
+                partition: Some(1),
+                timestamp: None,
+            }
+        );
+        assert_eq!(
+            records[1].1.as_ref().unwrap(),
+            &ImportRecord {
+                payload: Some("p2".into()),
+                ..Default::default()
+            }
+        );
+        assert!(records[2].1.is_err());
+        assert!(parse_csv("offset,timestamp\n1,2").is_err());
+    }
+
+    #[tokio::test]
+    async fn test_report_failures() {
+        let mut file = tempfile::NamedTempFile::new().unwrap();
+        file.write_all(
+            br#"{"key":"k1","payload":"p1"}
+{"key":"fail","payload":"p2"}
+{"key":"k3","payload":"p3"}"#,
+        )
+        .unwrap();
+        let res = import_records(
+            &MockProducer {},
+            "topic",
+            file.path(),
+            &ImportFormat::Jsonl,
+            &ProducerMode::String,
+        )
+        .await
+        .unwrap();
+        assert_eq!(res.record_count, 2);
+        assert_eq!(res.failures.len(), 1);
+        assert_eq!(res.failures[0].line, 2);
+    }
+}
@@ -0,0 +1,8 @@
+mod avro_serializer;
+mod client;
+mod importer;
+mod types;
+
+pub use client::{KafkaProducer, Producer};
+pub use importer::{import_records, ImportFailure, ImportFormat, ImportResult};
+pub use types::{ProducerMode, ProducerRecord, SchemaSelector};
@@ -0,0 +1,29 @@
+use serde::{Deserialize, Serialize};
+
+use crate::lib::types::RecordHeader;
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct ProducerRecord {
+    pub topic: String,
+    pub key: Option<String>,
+    pub payload: Option<String>,
+    #[serde(default)]
+    pub headers: Vec<RecordHeader>,
+    pub partition: Option<i32>,
+    pub timestamp: Option<i64>, //time in ms
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub enum ProducerMode {
+    String,
+    Avro(SchemaSelector),
+    // payloads in the standard avro json encoding, as decoded with AvroEncoding::AvroJson
+    AvroJson(SchemaSelector),
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub enum SchemaSelector {
+    // use the latest version of the subject
+    Subject(String),
+    Id(i32),
+}
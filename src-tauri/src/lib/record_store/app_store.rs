@@ -1,7 +1,58 @@
-use crate::lib::{types::ParsedKafkaRecord, Error, Result};
+use crate::lib::{
+    configuration::StoreLimits,
+    consumer::ConsumerOffsetConfiguration,
+    types::{ParsedKafkaRecord, RecordFormat},
+    Error, Result,
+};
 use parking_lot::FairMutex;
-use rusqlite::{named_params, Connection};
-use std::sync::Arc;
+use rusqlite::{
+    ffi::ErrorCode,
+    hooks::{AuthAction, AuthContext, Authorization},
+    named_params,
+    types::{FromSql, FromSqlError, FromSqlResult, ToSqlOutput, Type, ValueRef},
+    Connection, OptionalExtension, Row, Statement, ToSql,
+};
+use serde::{de::DeserializeOwned, Serialize};
+use serde_json::Value as JsonValue;
+use std::{
+    collections::HashMap,
+    path::Path,
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc,
+    },
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+// number of records read from the store at once by stream_records
+const STREAM_CHUNK_SIZE: i64 = 1000;
+
+// suffix of the temporary tables used by stream_records
+static SNAPSHOT_ID: AtomicUsize = AtomicUsize::new(0);
+
+// columns of the topic table, the other columns are payload projections
+const TOPIC_COLUMNS: [&str; 14] = [
+    "partition",
+    "offset",
+    "timestamp",
+    "key",
+    "payload",
+    "headers",
+    "key_format",
+    "payload_format",
+    "validation_errors",
+    "parse_error",
+    "key_schema_id",
+    "payload_schema_id",
+    "raw_key",
+    "raw_payload",
+];
+
+// size of a stored record, counted by the store limits. The text is measured in bytes
+const RECORD_SIZE: &str = "ifnull(length(CAST(key AS BLOB)), 0) + ifnull(length(CAST(payload AS BLOB)), 0) + ifnull(length(CAST(headers AS BLOB)), 0) + ifnull(length(raw_key), 0) + ifnull(length(raw_payload), 0)";
+
+// update of a topic table stored by a previous version of the app
+type Migration = fn(&Connection, &str, &str) -> Result<()>;
 
 pub struct Query {
     pub cluster_id: String,
@@ -10,55 +61,476 @@ pub struct Query {
     pub limit: i64,
     pub query_template: String,
 }
+
+// a previous consumption of a topic, the records are kept in the topic table
+#[derive(Serialize, Debug, Clone)]
+pub struct ConsumerSession {
+    #[serde(rename = "clusterId")]
+    pub cluster_id: String,
+    #[serde(rename = "topicName")]
+    pub topic_name: String,
+    #[serde(rename = "offsetConfig")]
+    pub offset_config: ConsumerOffsetConfiguration,
+    #[serde(rename = "startedAt")]
+    pub started_at: i64, //time in ms
+    #[serde(rename = "recordCount")]
+    pub record_count: usize,
+    // range of the timestamps of the stored records
+    #[serde(rename = "minTimestamp")]
+    pub min_timestamp: Option<i64>,
+    #[serde(rename = "maxTimestamp")]
+    pub max_timestamp: Option<i64>,
+}
+
 pub struct AppStore {
     conn: Arc<FairMutex<Connection>>,
+    topic_limits: StoreLimits,
+    global_limits: StoreLimits,
 }
 
 impl AppStore {
     pub fn new() -> Self {
+        let conn = Connection::open_in_memory().expect("Unable to initialize the in memory sqlite DB");
+        Self::create_store_tables(&conn).expect("Unable to create the sessions table");
+        Self::migrate_store(&conn).expect("Unable to migrate the store");
+        AppStore {
+            conn: Arc::new(FairMutex::new(conn)),
+            topic_limits: StoreLimits::default(),
+            global_limits: StoreLimits::default(),
+        }
+    }
+
+    // file backed store, the records survive to the app restarts
+    pub fn from_path(path: &Path) -> Result<Self> {
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+        let conn = Connection::open(path)?;
+        conn.execute_batch("PRAGMA journal_mode = WAL; PRAGMA synchronous = NORMAL;")?;
+        Self::create_store_tables(&conn)?;
+        Self::migrate_store(&conn)?;
+        Ok(AppStore {
+            conn: Arc::new(FairMutex::new(conn)),
+            topic_limits: StoreLimits::default(),
+            global_limits: StoreLimits::default(),
+        })
+    }
+
+    // limits applied by evict_records, to each topic table and to all of them
+    pub fn with_limits(self, topic_limits: StoreLimits, global_limits: StoreLimits) -> Self {
         AppStore {
-            conn: Arc::new(FairMutex::new(
-                Connection::open_in_memory().expect("Unable to initialize the in memory sqlite DB"),
-            )),
+            topic_limits,
+            global_limits,
+            ..self
+        }
+    }
+
+    pub fn get_topic_limits(&self) -> StoreLimits {
+        self.topic_limits
+    }
+
+    pub fn get_global_limits(&self) -> StoreLimits {
+        self.global_limits
+    }
+
+    fn create_store_tables(conn: &Connection) -> Result<()> {
+        conn.execute(
+            "CREATE TABLE IF NOT EXISTS sessions (
+                cluster_id      TEXT,
+                topic_name      TEXT,
+                offset_config   TEXT,
+                started_at      NUMBER,
+                PRIMARY KEY (cluster_id, topic_name))",
+            [],
+        )?;
+        // projections added to each topic table, to update them only when they change
+        conn.execute(
+            "CREATE TABLE IF NOT EXISTS projections (
+                cluster_id      TEXT,
+                topic_name      TEXT,
+                name            TEXT,
+                path            TEXT,
+                PRIMARY KEY (cluster_id, topic_name, name))",
+            [],
+        )?;
+        // size of each topic table, to check the limits without scanning it
+        conn.execute(
+            "CREATE TABLE IF NOT EXISTS topic_stats (
+                cluster_id      TEXT,
+                topic_name      TEXT,
+                record_count    NUMBER,
+                bytes           NUMBER,
+                evicted_count   NUMBER,
+                PRIMARY KEY (cluster_id, topic_name))",
+            [],
+        )?;
+        Ok(())
+    }
+
+    // update the topic tables stored by the previous versions of the app. The user_version of the
+    // store is the number of migrations already applied
+    fn migrate_store(conn: &Connection) -> Result<()> {
+        let migrations: [Migration; 2] = [
+            // the unique index on (partition, offset) can't be created with duplicated records
+            Self::remove_duplicated_records,
+            // the headers were stored as an object, losing their order and the duplicated keys
+            Self::convert_object_headers,
+        ];
+        let version: usize = conn.query_row("PRAGMA user_version", [], |row| row.get(0))?;
+        if version >= migrations.len() {
+            return Ok(());
+        }
+        let topics = Self::get_topic_tables(conn)?;
+        let tx = conn.unchecked_transaction()?;
+        for migration in &migrations[version..] {
+            for (cluster_id, topic_name) in &topics {
+                migration(&tx, cluster_id, topic_name)?;
+            }
         }
+        // the migrations change the size of the records
+        for (cluster_id, topic_name) in &topics {
+            tx.execute(
+                format!(
+                    "UPDATE topic_stats
+                    SET record_count = (SELECT count(*) FROM {table}), bytes = (SELECT ifnull(sum({}), 0) FROM {table})
+                    WHERE cluster_id = :cluster_id AND topic_name = :topic_name",
+                    RECORD_SIZE,
+                    table = Self::get_table_name(cluster_id, topic_name)
+                )
+                .as_str(),
+                named_params! { ":cluster_id": cluster_id, ":topic_name": topic_name },
+            )?;
+        }
+        tx.execute_batch(format!("PRAGMA user_version = {}", migrations.len()).as_str())?;
+        tx.commit()?;
+        Ok(())
+    }
+
+    // cluster and topic of the stored topic tables, named [cluster_id].[topic_name]
+    fn get_topic_tables(conn: &Connection) -> Result<Vec<(String, String)>> {
+        let mut stmt = conn.prepare("SELECT name FROM sqlite_master WHERE type = 'table' AND name LIKE '[%]'")?;
+        let names = stmt
+            .query_map([], |row| row.get::<_, String>(0))?
+            .collect::<rusqlite::Result<Vec<_>>>()?;
+        Ok(names
+            .iter()
+            .filter_map(|name| name.strip_prefix('[')?.strip_suffix(']')?.split_once("].["))
+            .map(|(cluster_id, topic_name)| (cluster_id.to_string(), topic_name.to_string()))
+            .collect())
+    }
+
+    fn remove_duplicated_records(conn: &Connection, cluster_id: &str, topic_name: &str) -> Result<()> {
+        let table = Self::get_table_name(cluster_id, topic_name);
+        let removed = conn.execute(
+            format!("DELETE FROM {table} WHERE rowid NOT IN (SELECT min(rowid) FROM {table} GROUP BY partition, offset)")
+                .as_str(),
+            [],
+        )?;
+        let index_name = Self::get_search_index_name(cluster_id, topic_name);
+        let has_index: bool = conn.query_row(
+            "SELECT count(*) > 0 FROM sqlite_master WHERE name = :name",
+            named_params! { ":name": index_name },
+            |row| row.get(0),
+        )?;
+        if removed > 0 && has_index {
+            conn.execute(
+                format!(
+                    "DELETE FROM {} WHERE rowid NOT IN (SELECT rowid FROM {table})",
+                    Self::quote_identifier(&index_name)
+                )
+                .as_str(),
+                [],
+            )?;
+        }
+        Ok(())
+    }
+
+    fn convert_object_headers(conn: &Connection, cluster_id: &str, topic_name: &str) -> Result<()> {
+        let table = Self::get_table_name(cluster_id, topic_name);
+        conn.execute(
+            format!(
+                "UPDATE {table} SET headers = (
+                    SELECT json_group_array(json_object('key', h.key, 'value', h.value))
+                    FROM json_each({table}.headers) AS h
+                ) WHERE json_type(headers) = 'object'"
+            )
+            .as_str(),
+            [],
+        )?;
+        Ok(())
     }
 
     pub async fn create_topic_table(&self, cluster_id: &str, topic_name: &str) -> Result<()> {
-        self.conn
-            .lock()
+        let connection = self.conn.lock();
+        connection
             .execute(
                 format!(
-                    "CREATE TABLE {} (
+                    "CREATE TABLE IF NOT EXISTS {} (
                         partition   NUMBER,
                         offset      NUMBER,
                         timestamp   NUMBER,
                         key         TEXT,
-                        payload     TEXT)",
+                        payload     TEXT,
+                        headers     TEXT,
+                        key_format  TEXT,
+                        payload_format TEXT,
+                        validation_errors TEXT,
+                        parse_error TEXT,
+                        key_schema_id NUMBER,
+                        payload_schema_id NUMBER,
+                        raw_key     BLOB,
+                        raw_payload BLOB)",
                     Self::get_table_name(cluster_id, topic_name)
                 )
                 .as_str(),
                 [],
             )
             .unwrap_or_else(|_| panic!("Unable to create the table for {} {}", cluster_id, topic_name));
-        Ok(())
+        // a record is stored only once, consuming overlapping ranges of offsets
+        connection.execute(
+            format!(
+                "CREATE UNIQUE INDEX IF NOT EXISTS \'[{}].[{}].unique\' ON {} (partition, offset)",
+                cluster_id,
+                topic_name,
+                Self::get_table_name(cluster_id, topic_name)
+            )
+            .as_str(),
+            [],
+        )?;
+        Self::create_search_index(&connection, cluster_id, topic_name)?;
+        Self::create_topic_stats(&connection, cluster_id, topic_name)
     }
 
-    pub async fn insert_record(&self, cluster_id: &str, topic_name: &str, record: &ParsedKafkaRecord) -> Result<()> {
-        self.conn.lock().execute(
+    // the records stored before the stats existed are counted when they are created
+    fn create_topic_stats(connection: &Connection, cluster_id: &str, topic_name: &str) -> Result<()> {
+        let exists: bool = connection.query_row(
+            "SELECT count(*) > 0 FROM topic_stats WHERE cluster_id = :cluster_id AND topic_name = :topic_name",
+            named_params! { ":cluster_id": cluster_id, ":topic_name": topic_name },
+            |row| row.get(0),
+        )?;
+        if exists {
+            return Ok(());
+        }
+        connection.execute(
             format!(
-                "INSERT INTO {} (partition, offset, timestamp, key, payload) 
-                VALUES (:partition, :offset, :timestamp, :key, :payload)",
+                "INSERT INTO topic_stats (cluster_id, topic_name, record_count, bytes, evicted_count)
+                SELECT :cluster_id, :topic_name, count(*), ifnull(sum({}), 0), 0 FROM {}",
+                RECORD_SIZE,
                 Self::get_table_name(cluster_id, topic_name)
             )
             .as_str(),
-            named_params! {
-                ":partition": &record.partition,
-                ":offset": &record.offset,
-                ":timestamp": &record.timestamp,
-                ":key": &record.key,
-                ":payload": &record.payload,
-            },
+            named_params! { ":cluster_id": cluster_id, ":topic_name": topic_name },
+        )?;
+        Ok(())
+    }
+
+    // full text index over the key, the payload and the header values of the records. It's updated
+    // by insert_records and not by triggers: fts5 flushes its pending changes at each trigger savepoint
+    fn create_search_index(connection: &Connection, cluster_id: &str, topic_name: &str) -> Result<()> {
+        let index_name = Self::get_search_index_name(cluster_id, topic_name);
+        // the stores created before the batched inserts still have the triggers updating the index,
+        // they would index each record twice
+        connection.execute_batch(
+            format!(
+                "DROP TRIGGER IF EXISTS {};
+                DROP TRIGGER IF EXISTS {};",
+                Self::quote_identifier(&format!("{}_insert", index_name)),
+                Self::quote_identifier(&format!("{}_delete", index_name)),
+            )
+            .as_str(),
+        )?;
+        let exists: bool = connection.query_row(
+            "SELECT count(*) > 0 FROM sqlite_master WHERE name = :name",
+            named_params! { ":name": index_name },
+            |row| row.get(0),
+        )?;
+        if exists {
+            return Ok(());
+        }
+        let (table, index) = (
+            Self::get_table_name(cluster_id, topic_name),
+            Self::quote_identifier(&index_name),
+        );
+        // the records stored before the index existed are indexed when it is created
+        let tx = connection.unchecked_transaction()?;
+        tx.execute_batch(
+            format!(
+                "CREATE VIRTUAL TABLE {index} USING fts5(key, payload, headers);
+                INSERT INTO {index} (rowid, key, payload, headers)
+                SELECT rowid, key, payload, (SELECT group_concat(json_extract(value, '$.value'), ' ') FROM json_each(t.headers)) FROM {table} AS t;"
+            )
+            .as_str(),
         )?;
+        tx.commit()?;
+        Ok(())
+    }
+
+    // expose the json paths of the payload as indexed virtual columns of the topic table
+    pub async fn set_projections(
+        &self,
+        cluster_id: &str,
+        topic_name: &str,
+        projections: &HashMap<String, String>,
+    ) -> Result<()> {
+        // the table could have been dropped deleting the session
+        self.create_topic_table(cluster_id, topic_name).await?;
+        let connection = self.conn.lock();
+        for (name, path) in projections {
+            Self::validate_projection(&connection, name, path)?;
+        }
+        let table_name = Self::get_table_name(cluster_id, topic_name);
+        let current: HashMap<String, String> = connection
+            .prepare("SELECT name, path FROM projections WHERE cluster_id = :cluster_id AND topic_name = :topic_name")?
+            .query_map(
+                named_params! { ":cluster_id": cluster_id, ":topic_name": topic_name },
+                |row| Ok((row.get(0)?, row.get(1)?)),
+            )?
+            .collect::<rusqlite::Result<_>>()?;
+        let tx = connection.unchecked_transaction()?;
+        for (name, path) in &current {
+            if projections.get(name) == Some(path) {
+                continue;
+            }
+            let index_name = Self::get_index_name(cluster_id, topic_name, name);
+            tx.execute(format!("DROP INDEX IF EXISTS {}", index_name).as_str(), [])?;
+            tx.execute(
+                format!("ALTER TABLE {} DROP COLUMN \"{}\"", table_name, name).as_str(),
+                [],
+            )?;
+            tx.execute(
+                "DELETE FROM projections WHERE cluster_id = :cluster_id AND topic_name = :topic_name AND name = :name",
+                named_params! { ":cluster_id": cluster_id, ":topic_name": topic_name, ":name": name },
+            )?;
+        }
+        for (name, path) in projections {
+            if current.get(name) == Some(path) {
+                continue;
+            }
+            // json_extract fails on the payloads that are not json
+            tx.execute(
+                format!(
+                    "ALTER TABLE {} ADD COLUMN \"{}\" GENERATED ALWAYS AS (CASE WHEN json_valid(payload) THEN json_extract(payload, '{}') END) VIRTUAL",
+                    table_name,
+                    name,
+                    path.replace('\'', "''")
+                )
+                .as_str(),
+                [],
+            )?;
+            tx.execute(
+                format!(
+                    "CREATE INDEX {} ON {} (\"{}\")",
+                    Self::get_index_name(cluster_id, topic_name, name),
+                    table_name,
+                    name
+                )
+                .as_str(),
+                [],
+            )?;
+            tx.execute(
+                "INSERT INTO projections (cluster_id, topic_name, name, path) VALUES (:cluster_id, :topic_name, :name, :path)",
+                named_params! { ":cluster_id": cluster_id, ":topic_name": topic_name, ":name": name, ":path": path },
+            )?;
+        }
+        tx.commit()?;
+        Ok(())
+    }
+
+    fn validate_projection(connection: &Connection, name: &str, path: &str) -> Result<()> {
+        let is_identifier = name.starts_with(|c: char| c.is_ascii_alphabetic() || c == '_')
+            && name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_');
+        if !is_identifier || TOPIC_COLUMNS.contains(&name.to_lowercase().as_str()) {
+            return Err(Error::SqlError {
+                message: format!(
+                    "Invalid projection name {}, use letters, digits and underscores and a name different from the record columns",
+                    name
+                ),
+            });
+        }
+        // sqlite validates the path only extracting it from a json
+        connection
+            .query_row(
+                "SELECT json_extract('{}', :path)",
+                named_params! { ":path": path },
+                |_| Ok(()),
+            )
+            .map_err(|err| Error::SqlError {
+                message: format!("Invalid json path {} for the projection {}\n{}", path, name, err),
+            })
+    }
+
+    // store the records in a single transaction, reusing the statement cached by the connection.
+    // The records already stored with the same partition and offset are skipped
+    pub async fn insert_records(&self, cluster_id: &str, topic_name: &str, records: &[ParsedKafkaRecord]) -> Result<()> {
+        let connection = self.conn.lock();
+        let tx = connection.unchecked_transaction()?;
+        {
+            let mut stmt = tx.prepare_cached(
+                format!(
+                    "INSERT OR IGNORE INTO {} (partition, offset, timestamp, key, payload, headers, key_format, payload_format, validation_errors, parse_error, key_schema_id, payload_schema_id, raw_key, raw_payload) 
+                    VALUES (:partition, :offset, :timestamp, :key, :payload, :headers, :key_format, :payload_format, :validation_errors, :parse_error, :key_schema_id, :payload_schema_id, :raw_key, :raw_payload)",
+                    Self::get_table_name(cluster_id, topic_name)
+                )
+                .as_str(),
+            )?;
+            let mut search_stmt = tx.prepare_cached(
+                format!(
+                    "INSERT INTO {} (rowid, key, payload, headers) VALUES (:rowid, :key, :payload, :headers)",
+                    Self::quote_identifier(&Self::get_search_index_name(cluster_id, topic_name))
+                )
+                .as_str(),
+            )?;
+            let (mut first_rowid, mut inserted) = (None, 0);
+            for record in records {
+                let changes = stmt.execute(named_params! {
+                    ":partition": &record.partition,
+                    ":offset": &record.offset,
+                    ":timestamp": &record.timestamp,
+                    ":key": &record.key,
+                    ":payload": &record.payload,
+                    ":headers": serde_json::to_string(&record.headers)?,
+                    ":key_format": &record.key_format,
+                    ":payload_format": &record.payload_format,
+                    ":validation_errors": record.validation_errors.as_ref().map(serde_json::to_string).transpose()?,
+                    ":parse_error": &record.parse_error,
+                    ":key_schema_id": &record.key_schema_id,
+                    ":payload_schema_id": &record.payload_schema_id,
+                    ":raw_key": &record.raw_key,
+                    ":raw_payload": &record.raw_payload,
+                })?;
+                if changes == 0 {
+                    continue;
+                }
+                let rowid = tx.last_insert_rowid();
+                search_stmt.execute(named_params! {
+                    ":rowid": rowid,
+                    ":key": &record.key,
+                    ":payload": &record.payload,
+                    ":headers": record.headers.iter().filter_map(|h| h.value.as_deref()).collect::<Vec<_>>().join(" "),
+                })?;
+                first_rowid.get_or_insert(rowid);
+                inserted += 1;
+            }
+            if let Some(first_rowid) = first_rowid {
+                tx.prepare_cached(
+                    format!(
+                        "UPDATE topic_stats SET record_count = record_count + :count,
+                        bytes = bytes + (SELECT ifnull(sum({}), 0) FROM {} WHERE rowid >= :first_rowid)
+                        WHERE cluster_id = :cluster_id AND topic_name = :topic_name",
+                        RECORD_SIZE,
+                        Self::get_table_name(cluster_id, topic_name)
+                    )
+                    .as_str(),
+                )?
+                .execute(named_params! {
+                    ":count": inserted,
+                    ":first_rowid": first_rowid,
+                    ":cluster_id": cluster_id,
+                    ":topic_name": topic_name,
+                })?;
+            }
+        }
+        tx.commit()?;
         Ok(())
     }
 
@@ -69,36 +541,157 @@ impl AppStore {
         offset: i64,
         limit: i64,
     ) -> Result<Vec<ParsedKafkaRecord>> {
+        let query_template = Self::get_default_query_template(&self.conn.lock(), cluster_id, topic_name)?;
         self.query_records(&Query {
             cluster_id: cluster_id.into(),
             topic_name: topic_name.into(),
             offset,
             limit,
-            query_template: "SELECT partition, offset, timestamp, key, payload FROM {:topic} ORDER BY timestamp desc LIMIT {:limit} OFFSET {:offset}".into(),
+            query_template,
         })
         .await
     }
 
+    // the record columns and the payload projections. The raw key and payload are only read by the export
+    fn get_default_query_template(connection: &Connection, cluster_id: &str, topic_name: &str) -> Result<String> {
+        let projections: Vec<String> = connection
+            .prepare(
+                "SELECT name FROM projections WHERE cluster_id = :cluster_id AND topic_name = :topic_name ORDER BY name",
+            )?
+            .query_map(
+                named_params! { ":cluster_id": cluster_id, ":topic_name": topic_name },
+                |row| row.get(0),
+            )?
+            .collect::<rusqlite::Result<_>>()?;
+        let columns: Vec<String> = TOPIC_COLUMNS
+            .iter()
+            .filter(|column| !["raw_key", "raw_payload"].contains(column))
+            .map(|column| column.to_string())
+            .chain(projections.iter().map(|name| format!("\"{}\"", name)))
+            .collect();
+        Ok(format!(
+            "SELECT {} FROM {{:topic}} ORDER BY timestamp desc LIMIT {{:limit}} OFFSET {{:offset}}",
+            columns.join(", ")
+        ))
+    }
+
     pub async fn query_records(&self, query: &Query) -> Result<Vec<ParsedKafkaRecord>> {
+        self.run_query(
+            query,
+            |sql| sql.into(),
+            |stmt| Self::read_records(stmt, &query.topic_name),
+        )
+    }
+
+    // call on_records with the records returned by the query, in chunks of STREAM_CHUNK_SIZE records.
+    // The records are copied to a temporary table first, so the connection is only locked
+    // while reading each chunk and the new records don't change the result
+    pub fn stream_records(
+        &self,
+        query: &Query,
+        on_records: impl FnMut(Vec<ParsedKafkaRecord>) -> Result<()>,
+    ) -> Result<()> {
+        let snapshot = format!("temp.[snapshot_{}]", SNAPSHOT_ID.fetch_add(1, Ordering::Relaxed));
+        let res = self
+            .create_snapshot(query, &snapshot)
+            .and_then(|_| self.read_snapshot(&snapshot, &query.topic_name, on_records));
+        let dropped = self
+            .conn
+            .lock()
+            .execute(&format!("DROP TABLE IF EXISTS {}", snapshot), []);
+        res.and(dropped.map(|_| ()).map_err(Error::from))
+    }
+
+    fn create_snapshot(&self, query: &Query, snapshot: &str) -> Result<()> {
+        // the authorizer checks the user query, the snapshot is created without it
+        // because the copy writes to the temp schema
+        self.run_query(query, |sql| sql.into(), |_| Ok(()))?;
+        let parsed_query = ParsedQuery::parse(query)?;
         let connection = self.conn.lock();
-        let parsed_query = Self::parse_query(query);
-        let mut stmt = connection.prepare(&parsed_query)?;
+        let mut stmt = connection.prepare(&format!("CREATE TABLE {} AS {}", snapshot, parsed_query.sql))?;
+        Self::bind_query_parameters(&mut stmt, query)?;
+        stmt.raw_execute()?;
+        Ok(())
+    }
 
-        let records_iter = stmt.query_map([], |row| {
-            Ok(ParsedKafkaRecord {
-                topic: query.topic_name.clone(),
-                partition: row.get(0)?,
-                offset: row.get(1)?,
-                timestamp: row.get(2)?,
-                key: row.get(3)?,
-                payload: row.get(4)?,
-            })
-        })?;
-        let mut records = Vec::new();
-        for r in records_iter {
-            records.push(r?);
+    // the rowids of the snapshot follow the order of the query, from 1 to the number of records
+    fn read_snapshot(
+        &self,
+        snapshot: &str,
+        topic_name: &str,
+        mut on_records: impl FnMut(Vec<ParsedKafkaRecord>) -> Result<()>,
+    ) -> Result<()> {
+        let mut last_rowid = 0;
+        loop {
+            let records = {
+                let connection = self.conn.lock();
+                let mut stmt = connection.prepare(&format!(
+                    "SELECT * FROM {} WHERE rowid > ?1 ORDER BY rowid LIMIT ?2",
+                    snapshot
+                ))?;
+                stmt.raw_bind_parameter(1, last_rowid)?;
+                stmt.raw_bind_parameter(2, STREAM_CHUNK_SIZE)?;
+                Self::read_records(&mut stmt, topic_name)?
+            };
+            if records.is_empty() {
+                return Ok(());
+            }
+            last_rowid += records.len() as i64;
+            on_records(records)?;
         }
-        Ok(records)
+    }
+
+    // distinct ids of the payload schemas of the records returned by the query
+    pub async fn get_payload_schema_ids(&self, query: &Query) -> Result<Vec<i32>> {
+        self.run_query(
+            query,
+            |sql| {
+                format!(
+                    "SELECT DISTINCT payload_schema_id FROM ({}\n) WHERE payload_schema_id IS NOT NULL",
+                    sql
+                )
+            },
+            |stmt| {
+                let mut ids = Vec::new();
+                let mut rows = stmt.raw_query();
+                while let Some(row) = rows.next()? {
+                    ids.push(row.get(0)?);
+                }
+                Ok(ids)
+            },
+        )
+    }
+
+    // records matching all the words of the search, the best matches first
+    pub async fn search_records(
+        &self,
+        cluster_id: &str,
+        topic_name: &str,
+        search: &str,
+        offset: i64,
+        limit: i64,
+    ) -> Result<Vec<ParsedKafkaRecord>> {
+        // each word is a quoted string, so the fts5 query syntax is matched as plain text
+        let terms: Vec<String> = search
+            .split_whitespace()
+            .map(|term| format!("\"{}\"", term.replace('"', "\"\"")))
+            .collect();
+        if terms.is_empty() {
+            return Ok(vec![]);
+        }
+        let connection = self.conn.lock();
+        let index = Self::quote_identifier(&Self::get_search_index_name(cluster_id, topic_name));
+        let mut stmt = connection.prepare(
+            format!(
+                "SELECT t.* FROM {index} JOIN {} AS t ON t.rowid = {index}.rowid WHERE {index} MATCH ?1 ORDER BY rank LIMIT ?2 OFFSET ?3",
+                Self::get_table_name(cluster_id, topic_name)
+            )
+            .as_str(),
+        )?;
+        stmt.raw_bind_parameter(1, terms.join(" "))?;
+        stmt.raw_bind_parameter(2, limit)?;
+        stmt.raw_bind_parameter(3, offset)?;
+        Self::read_records(&mut stmt, topic_name)
     }
 
     pub async fn get_size(&self, cluster_id: &str, topic_name: &str) -> Result<usize> {
@@ -113,123 +706,759 @@ impl AppStore {
     }
 
     pub async fn get_size_with_query(&self, query: &Query) -> Result<usize> {
-        let connection = self.conn.lock();
-        let mut stmt = connection.prepare(format!("SELECT count(*) FROM ({})", Self::parse_query(query)).as_str())?;
-        let rows: Vec<_> = stmt.query_map([], |row| row.get::<_, i64>(0))?.collect();
-        if let Some(Ok(size)) = rows.first() {
-            Ok(*size as usize)
-        } else {
-            Err(Error::SqlError {
-                message: "Unable to get the table size".into(),
-            })
-        }
+        self.run_query(
+            query,
+            |sql| format!("SELECT count(*) FROM ({}\n)", sql),
+            |stmt| match stmt.raw_query().next()? {
+                Some(row) => Ok(row.get::<_, i64>(0)? as usize),
+                None => Err(Error::SqlError {
+                    message: "Unable to get the table size".into(),
+                }),
+            },
+        )
+    }
+
+    // check that the query template is a valid read only query on the topic table
+    pub async fn validate_query(&self, query: &Query) -> Result<()> {
+        self.run_query(query, |sql| sql.into(), |_| Ok(()))
     }
 
     pub async fn clear(&self, cluster_id: &str, topic_name: &str) -> Result<()> {
-        self.conn
-            .lock()
+        // the table could have been dropped deleting the session
+        self.create_topic_table(cluster_id, topic_name).await?;
+        let connection = self.conn.lock();
+        connection
             .execute(
                 format!("DELETE FROM {}", Self::get_table_name(cluster_id, topic_name)).as_str(),
                 [],
             )
             .unwrap_or_else(|_| panic!("Unable to create the table for {} {}", cluster_id, topic_name));
+        // recreating the search index is faster than deleting its rows
+        connection.execute(
+            format!(
+                "DROP TABLE {}",
+                Self::quote_identifier(&Self::get_search_index_name(cluster_id, topic_name))
+            )
+            .as_str(),
+            [],
+        )?;
+        Self::create_search_index(&connection, cluster_id, topic_name)?;
+        connection.execute(
+            "UPDATE topic_stats SET record_count = 0, bytes = 0, evicted_count = 0
+            WHERE cluster_id = :cluster_id AND topic_name = :topic_name",
+            named_params! { ":cluster_id": cluster_id, ":topic_name": topic_name },
+        )?;
         Ok(())
     }
 
-    fn parse_query(query: &Query) -> String {
-        let Query {
+    // evict the records of the topic exceeding the topic limits, then the records of the largest
+    // topics exceeding the global limits. Returns the number of evicted records
+    pub async fn evict_records(&self, cluster_id: &str, topic_name: &str) -> Result<usize> {
+        let connection = self.conn.lock();
+        let tx = connection.unchecked_transaction()?;
+        let now = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|d| d.as_millis() as i64)
+            .unwrap_or_default();
+        let mut evicted = 0;
+        // the age is compared to the record timestamp
+        if let Some(max_age) = self.topic_limits.max_age {
+            evicted += Self::delete_older_records(&tx, cluster_id, topic_name, now - max_age)?;
+        }
+        if let Some(max_age) = self.global_limits.max_age {
+            for (cluster_id, topic_name, _, _) in Self::list_topic_stats(&tx)? {
+                evicted += Self::delete_older_records(&tx, &cluster_id, &topic_name, now - max_age)?;
+            }
+        }
+        let (record_count, bytes) = Self::get_topic_stats(&tx, cluster_id, topic_name)?;
+        evicted += Self::delete_oldest_records(
+            &tx,
             cluster_id,
             topic_name,
-            offset,
-            limit,
-            query_template,
-        } = query;
-        let query = query_template
-            .replace("{:topic}", Self::get_table_name(cluster_id, topic_name).as_str())
-            .replace("{:limit}", limit.to_string().as_str())
-            .replace("{:offset}", offset.to_string().as_str());
-        let query = query.trim();
-        if query.ends_with(';') {
-            let mut chars = query.chars();
-            chars.next_back();
-            chars.as_str().into()
-        } else {
-            query.into()
+            get_excess(record_count, self.topic_limits.max_records),
+            get_excess(bytes, self.topic_limits.max_bytes),
+        )?;
+        let topics = Self::list_topic_stats(&tx)?;
+        let mut excess_records = get_excess(topics.iter().map(|t| t.2).sum(), self.global_limits.max_records);
+        let mut excess_bytes = get_excess(topics.iter().map(|t| t.3).sum(), self.global_limits.max_bytes);
+        for (cluster_id, topic_name, record_count, bytes) in topics {
+            if excess_records == 0 && excess_bytes == 0 {
+                break;
+            }
+            let (topic_records, topic_bytes) = (excess_records.min(record_count), excess_bytes.min(bytes));
+            evicted += Self::delete_oldest_records(&tx, &cluster_id, &topic_name, topic_records, topic_bytes)?;
+            excess_records -= topic_records;
+            excess_bytes -= topic_bytes;
         }
+        tx.commit()?;
+        Ok(evicted)
     }
 
-    fn get_table_name(cluster_id: &str, topic_name: &str) -> String {
-        format!("\'[{}].[{}]\'", cluster_id, topic_name)
+    // records evicted since the topic table was cleared
+    pub async fn get_evicted_count(&self, cluster_id: &str, topic_name: &str) -> Result<usize> {
+        let evicted_count = self
+            .conn
+            .lock()
+            .query_row(
+                "SELECT evicted_count FROM topic_stats WHERE cluster_id = :cluster_id AND topic_name = :topic_name",
+                named_params! { ":cluster_id": cluster_id, ":topic_name": topic_name },
+                |row| row.get(0),
+            )
+            .optional()?;
+        Ok(evicted_count.unwrap_or(0))
     }
-}
 
-#[cfg(test)]
-mod tests {
-    use crate::lib::{record_store::app_store::Query, types::ParsedKafkaRecord};
+    // offset of the last record stored in each partition
+    pub async fn get_last_offsets(&self, cluster_id: &str, topic_name: &str) -> Result<HashMap<i32, i64>> {
+        let connection = self.conn.lock();
+        let offsets = connection
+            .prepare(
+                format!(
+                    "SELECT partition, max(offset) FROM {} GROUP BY partition",
+                    Self::get_table_name(cluster_id, topic_name)
+                )
+                .as_str(),
+            )?
+            .query_map([], |row| Ok((row.get(0)?, row.get(1)?)))?
+            .collect::<rusqlite::Result<_>>()?;
+        Ok(offsets)
+    }
 
-    use super::AppStore;
+    fn get_topic_stats(connection: &Connection, cluster_id: &str, topic_name: &str) -> Result<(usize, usize)> {
+        Ok(connection.query_row(
+            "SELECT record_count, bytes FROM topic_stats WHERE cluster_id = :cluster_id AND topic_name = :topic_name",
+            named_params! { ":cluster_id": cluster_id, ":topic_name": topic_name },
+            |row| Ok((row.get(0)?, row.get(1)?)),
+        )?)
+    }
 
-    #[tokio::test]
-    async fn test_create_table() {
-        let db = AppStore::new();
-        let res = db.create_topic_table("cluster_id_example", "topic_name_example").await;
-        assert!(res.is_ok())
+    // cluster id, topic name, record count and bytes of each topic table, the largest first
+    fn list_topic_stats(connection: &Connection) -> Result<Vec<(String, String, usize, usize)>> {
+        Ok(connection
+            .prepare("SELECT cluster_id, topic_name, record_count, bytes FROM topic_stats ORDER BY bytes desc")?
+            .query_map([], |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?, row.get(3)?)))?
+            .collect::<rusqlite::Result<_>>()?)
     }
 
-    #[tokio::test]
-    async fn test_insert_and_get_record() {
-        // arrange
-        let (cluster_id, topic_name) = ("cluster_id_example", "topic_name_example");
-        let db = AppStore::new();
-        db.create_topic_table(&cluster_id, &topic_name)
-            .await
-            .expect("Unable to create the table");
-        let test_record = get_test_record(topic_name, 0);
-        // act
-        let res = db.insert_record(cluster_id, topic_name, &test_record).await;
-        let records_back = db.get_records(cluster_id, topic_name, 0, 1000).await.unwrap();
-        // assert
-        assert!(res.is_ok());
-        assert!(records_back.len() == 1);
-        assert_eq!(records_back[0], test_record);
+    fn delete_older_records(
+        connection: &Connection,
+        cluster_id: &str,
+        topic_name: &str,
+        min_timestamp: i64,
+    ) -> Result<usize> {
+        Self::delete_records(
+            connection,
+            cluster_id,
+            topic_name,
+            "timestamp < :min_timestamp",
+            named_params! { ":min_timestamp": min_timestamp },
+        )
     }
 
-    #[tokio::test]
-    async fn test_get_size() {
-        // arrange
-        let (cluster_id, topic_name) = ("cluster_id_example", "topic_name_example");
-        let db = AppStore::new();
-        db.create_topic_table(&cluster_id, &topic_name)
-            .await
-            .expect("Unable to create the table");
-        let test_record = get_test_record(topic_name, 0);
-        // act
-        db.insert_record(cluster_id, topic_name, &test_record).await.unwrap();
-        db.insert_record(cluster_id, topic_name, &test_record).await.unwrap();
-        db.insert_record(cluster_id, topic_name, &test_record).await.unwrap();
-        let table_size = db.get_size(cluster_id, topic_name).await.unwrap();
-        // assert
-        assert_eq!(table_size, 3);
+    // delete the records in insertion order, until both the record count and the bytes are freed
+    fn delete_oldest_records(
+        connection: &Connection,
+        cluster_id: &str,
+        topic_name: &str,
+        record_count: usize,
+        bytes: usize,
+    ) -> Result<usize> {
+        if record_count == 0 && bytes == 0 {
+            return Ok(0);
+        }
+        let mut stmt = connection.prepare(
+            format!(
+                "SELECT rowid, {} FROM {} ORDER BY rowid",
+                RECORD_SIZE,
+                Self::get_table_name(cluster_id, topic_name)
+            )
+            .as_str(),
+        )?;
+        let mut rows = stmt.query([])?;
+        let (mut max_rowid, mut deleted_records, mut deleted_bytes) = (None, 0, 0);
+        while deleted_records < record_count || deleted_bytes < bytes {
+            match rows.next()? {
+                Some(row) => {
+                    max_rowid = Some(row.get::<_, i64>(0)?);
+                    deleted_records += 1;
+                    deleted_bytes += row.get::<_, usize>(1)?;
+                }
+                None => break,
+            }
+        }
+        match max_rowid {
+            Some(max_rowid) => Self::delete_records(
+                connection,
+                cluster_id,
+                topic_name,
+                "rowid <= :max_rowid",
+                named_params! { ":max_rowid": max_rowid },
+            ),
+            None => Ok(0),
+        }
     }
 
-    #[tokio::test]
-    async fn test_get_size_with_query() {
-        // arrange
-        let (cluster_id, topic_name) = ("cluster_id_example", "topic_name_example");
-        let db = AppStore::new();
-        db.create_topic_table(&cluster_id, &topic_name)
-            .await
-            .expect("Unable to create the table");
-        // act
-        db.insert_record(cluster_id, topic_name, &get_test_record(topic_name, 1))
-            .await
-            .unwrap();
-        db.insert_record(cluster_id, topic_name, &get_test_record(topic_name, 0))
-            .await
-            .unwrap();
-        db.insert_record(cluster_id, topic_name, &get_test_record(topic_name, 0))
-            .await
-            .unwrap();
+    // delete the records matching the condition from the topic table and from the search index
+    fn delete_records(
+        connection: &Connection,
+        cluster_id: &str,
+        topic_name: &str,
+        condition: &str,
+        params: &[(&str, &dyn ToSql)],
+    ) -> Result<usize> {
+        let table = Self::get_table_name(cluster_id, topic_name);
+        connection.execute(
+            format!(
+                "DELETE FROM {} WHERE rowid IN (SELECT rowid FROM {} WHERE {})",
+                Self::quote_identifier(&Self::get_search_index_name(cluster_id, topic_name)),
+                table,
+                condition
+            )
+            .as_str(),
+            params,
+        )?;
+        let (record_count, bytes) = connection
+            .prepare(format!("DELETE FROM {} WHERE {} RETURNING {}", table, condition, RECORD_SIZE).as_str())?
+            .query_map(params, |row| row.get::<_, usize>(0))?
+            .try_fold((0, 0), |(count, bytes), size| {
+                size.map(|size| (count + 1, bytes + size))
+            })?;
+        if record_count > 0 {
+            connection.execute(
+                "UPDATE topic_stats SET record_count = record_count - :count, bytes = bytes - :bytes,
+                evicted_count = evicted_count + :count WHERE cluster_id = :cluster_id AND topic_name = :topic_name",
+                named_params! {
+                    ":count": record_count,
+                    ":bytes": bytes,
+                    ":cluster_id": cluster_id,
+                    ":topic_name": topic_name,
+                },
+            )?;
+        }
+        Ok(record_count)
+    }
+
+    pub async fn save_session(
+        &self,
+        cluster_id: &str,
+        topic_name: &str,
+        offset_config: &ConsumerOffsetConfiguration,
+        started_at: i64,
+    ) -> Result<()> {
+        self.conn.lock().execute(
+            "INSERT OR REPLACE INTO sessions (cluster_id, topic_name, offset_config, started_at)
+            VALUES (:cluster_id, :topic_name, :offset_config, :started_at)",
+            named_params! {
+                ":cluster_id": cluster_id,
+                ":topic_name": topic_name,
+                ":offset_config": serde_json::to_string(offset_config)?,
+                ":started_at": started_at,
+            },
+        )?;
+        Ok(())
+    }
+
+    pub async fn list_sessions(&self) -> Result<Vec<ConsumerSession>> {
+        let connection = self.conn.lock();
+        let mut stmt = connection
+            .prepare("SELECT cluster_id, topic_name, offset_config, started_at FROM sessions ORDER BY started_at desc")?;
+        let rows = stmt.query_map([], |row| {
+            Ok((
+                row.get::<_, String>(0)?,
+                row.get::<_, String>(1)?,
+                Self::get_json_column::<Option<ConsumerOffsetConfiguration>>(row, Some(2))?,
+                row.get::<_, i64>(3)?,
+            ))
+        })?;
+        let mut sessions = Vec::new();
+        for row in rows {
+            let (cluster_id, topic_name, offset_config, started_at) = row?;
+            let (record_count, min_timestamp, max_timestamp) = connection
+                .query_row(
+                    format!(
+                        "SELECT count(*), min(timestamp), max(timestamp) FROM {}",
+                        Self::get_table_name(&cluster_id, &topic_name)
+                    )
+                    .as_str(),
+                    [],
+                    |row| Ok((row.get::<_, i64>(0)? as usize, row.get(1)?, row.get(2)?)),
+                )
+                // the topic table is missing if the session was never started
+                .unwrap_or((0, None, None));
+            sessions.push(ConsumerSession {
+                cluster_id,
+                topic_name,
+                offset_config: offset_config.ok_or_else(|| Error::SqlError {
+                    message: "Missing offset configuration in the stored session".into(),
+                })?,
+                started_at,
+                record_count,
+                min_timestamp,
+                max_timestamp,
+            });
+        }
+        Ok(sessions)
+    }
+
+    // drop the stored records and the session
+    pub async fn delete_session(&self, cluster_id: &str, topic_name: &str) -> Result<()> {
+        let connection = self.conn.lock();
+        connection.execute(
+            format!("DROP TABLE IF EXISTS {}", Self::get_table_name(cluster_id, topic_name)).as_str(),
+            [],
+        )?;
+        connection.execute(
+            format!(
+                "DROP TABLE IF EXISTS {}",
+                Self::quote_identifier(&Self::get_search_index_name(cluster_id, topic_name))
+            )
+            .as_str(),
+            [],
+        )?;
+        for table in ["sessions", "projections", "topic_stats"] {
+            connection.execute(
+                format!(
+                    "DELETE FROM {} WHERE cluster_id = :cluster_id AND topic_name = :topic_name",
+                    table
+                )
+                .as_str(),
+                named_params! { ":cluster_id": cluster_id, ":topic_name": topic_name },
+            )?;
+        }
+        Ok(())
+    }
+
+    // run a user query on the shared connection. The authorizer only allows
+    // to read from the topic table and from the read-only json table functions
+    // (needed to filter the headers array), so the user can't change the store
+    fn run_query<T>(
+        &self,
+        query: &Query,
+        wrap: impl FnOnce(&str) -> String,
+        map: impl FnOnce(&mut Statement) -> Result<T>,
+    ) -> Result<T> {
+        let parsed_query = ParsedQuery::parse(query)?;
+        let connection = self.conn.lock();
+        let table_name = format!("[{}].[{}]", query.cluster_id, query.topic_name);
+        connection.authorizer(Some(move |ctx: AuthContext<'_>| match ctx.action {
+            AuthAction::Select | AuthAction::Function { .. } => Authorization::Allow,
+            AuthAction::Read { table_name: name, .. } if name == table_name => Authorization::Allow,
+            AuthAction::Read {
+                table_name: "json_each" | "json_tree",
+                ..
+            } => Authorization::Allow,
+            _ => Authorization::Deny,
+        }));
+        let res = Self::execute_query(&connection, query, &parsed_query, wrap, map);
+        connection.authorizer(None::<fn(AuthContext<'_>) -> Authorization>);
+        res
+    }
+
+    fn execute_query<T>(
+        connection: &Connection,
+        query: &Query,
+        parsed_query: &ParsedQuery,
+        wrap: impl FnOnce(&str) -> String,
+        map: impl FnOnce(&mut Statement) -> Result<T>,
+    ) -> Result<T> {
+        // prepare the user query alone first, to report the errors at the right position
+        connection
+            .prepare(&parsed_query.sql)
+            .map_err(|err| parsed_query.to_error(connection, err))?;
+        let mut stmt = connection.prepare(&wrap(&parsed_query.sql))?;
+        Self::bind_query_parameters(&mut stmt, query)?;
+        map(&mut stmt)
+    }
+
+    fn bind_query_parameters(stmt: &mut Statement, query: &Query) -> Result<()> {
+        for (name, value) in [(":limit", query.limit), (":offset", query.offset)] {
+            if let Some(index) = stmt.parameter_index(name)? {
+                stmt.raw_bind_parameter(index, value)?;
+            }
+        }
+        Ok(())
+    }
+
+    fn read_records(stmt: &mut Statement, topic_name: &str) -> Result<Vec<ParsedKafkaRecord>> {
+        let mut records = Vec::new();
+        Self::for_each_record(stmt, topic_name, |record| {
+            records.push(record);
+            Ok(())
+        })?;
+        Ok(records)
+    }
+
+    fn for_each_record(
+        stmt: &mut Statement,
+        topic_name: &str,
+        mut on_record: impl FnMut(ParsedKafkaRecord) -> Result<()>,
+    ) -> Result<()> {
+        // the columns after the payload are optional in the user query
+        let headers_index = stmt.column_index("headers").ok();
+        let key_format_index = stmt.column_index("key_format").ok();
+        let payload_format_index = stmt.column_index("payload_format").ok();
+        let validation_errors_index = stmt.column_index("validation_errors").ok();
+        let parse_error_index = stmt.column_index("parse_error").ok();
+        let key_schema_id_index = stmt.column_index("key_schema_id").ok();
+        let payload_schema_id_index = stmt.column_index("payload_schema_id").ok();
+        let raw_key_index = stmt.column_index("raw_key").ok();
+        let raw_payload_index = stmt.column_index("raw_payload").ok();
+        // the first five columns are always the record columns
+        let projection_columns: Vec<(usize, String)> = stmt
+            .column_names()
+            .into_iter()
+            .enumerate()
+            .skip(5)
+            .filter(|(_, name)| !TOPIC_COLUMNS.contains(name))
+            .map(|(index, name)| (index, name.to_string()))
+            .collect();
+
+        let mut rows = stmt.raw_query();
+        while let Some(row) = rows.next()? {
+            on_record(ParsedKafkaRecord {
+                topic: topic_name.into(),
+                partition: row.get(0)?,
+                offset: row.get(1)?,
+                timestamp: row.get(2)?,
+                key: row.get(3)?,
+                payload: row.get(4)?,
+                headers: Self::get_json_column(row, headers_index)?,
+                key_format: Self::get_optional_column(row, key_format_index)?,
+                payload_format: Self::get_optional_column(row, payload_format_index)?,
+                validation_errors: Self::get_json_column(row, validation_errors_index)?,
+                parse_error: Self::get_optional_column(row, parse_error_index)?,
+                key_schema_id: Self::get_optional_column(row, key_schema_id_index)?,
+                payload_schema_id: Self::get_optional_column(row, payload_schema_id_index)?,
+                raw_key: Self::get_optional_column(row, raw_key_index)?,
+                raw_payload: Self::get_optional_column(row, raw_payload_index)?,
+                projections: projection_columns
+                    .iter()
+                    .map(|(index, name)| Ok((name.clone(), Self::get_json_value(row, *index)?)))
+                    .collect::<rusqlite::Result<_>>()?,
+            })?;
+        }
+        Ok(())
+    }
+
+    fn get_json_column<T: DeserializeOwned + Default>(row: &Row, index: Option<usize>) -> rusqlite::Result<T> {
+        match index {
+            Some(index) => match row.get::<_, Option<String>>(index)? {
+                Some(raw) => serde_json::from_str(&raw)
+                    .map_err(|err| rusqlite::Error::FromSqlConversionFailure(index, Type::Text, Box::new(err))),
+                None => Ok(Default::default()),
+            },
+            None => Ok(Default::default()),
+        }
+    }
+
+    fn get_optional_column<T: FromSql>(row: &Row, index: Option<usize>) -> rusqlite::Result<Option<T>> {
+        match index {
+            Some(index) => row.get(index),
+            None => Ok(None),
+        }
+    }
+
+    // json_extract returns the json objects and arrays as text
+    fn get_json_value(row: &Row, index: usize) -> rusqlite::Result<JsonValue> {
+        Ok(match row.get_ref(index)? {
+            ValueRef::Null => JsonValue::Null,
+            ValueRef::Integer(value) => value.into(),
+            ValueRef::Real(value) => value.into(),
+            ValueRef::Text(value) | ValueRef::Blob(value) => String::from_utf8_lossy(value).into(),
+        })
+    }
+
+    fn get_table_name(cluster_id: &str, topic_name: &str) -> String {
+        format!("\'[{}].[{}]\'", cluster_id, topic_name)
+    }
+
+    fn get_search_index_name(cluster_id: &str, topic_name: &str) -> String {
+        format!("[{}].[{}].search", cluster_id, topic_name)
+    }
+
+    fn quote_identifier(name: &str) -> String {
+        format!("\"{}\"", name.replace('"', "\"\""))
+    }
+
+    fn get_index_name(cluster_id: &str, topic_name: &str, projection: &str) -> String {
+        format!("\'[{}].[{}].{}_index\'", cluster_id, topic_name, projection)
+    }
+}
+
+// amount over the limit, zero if there is no limit
+fn get_excess(value: usize, limit: Option<usize>) -> usize {
+    limit.map(|limit| value.saturating_sub(limit)).unwrap_or(0)
+}
+
+// sql generated from a query template. The table name is replaced in the sql while
+// the limit and the offset are bound as parameters
+struct ParsedQuery {
+    template: String,
+    sql: String,
+    placeholders: Vec<Placeholder>,
+}
+
+// position of a placeholder in the template and of its replacement in the sql
+struct Placeholder {
+    template_start: usize,
+    template_end: usize,
+    sql_start: usize,
+    sql_end: usize,
+}
+
+impl ParsedQuery {
+    fn parse(query: &Query) -> Result<Self> {
+        let template = query.query_template.trim_end();
+        let template = template.strip_suffix(';').unwrap_or(template);
+        let table_name = AppStore::get_table_name(&query.cluster_id, &query.topic_name);
+        let mut parsed_query = ParsedQuery {
+            template: template.into(),
+            sql: String::with_capacity(template.len()),
+            placeholders: vec![],
+        };
+        // sqlite only prepares the first statement, the others would be silently ignored
+        if let Some(end) = find_statement_end(template) {
+            return Err(Error::InvalidQuery {
+                message: "Only one statement is allowed in the query".into(),
+                position: Some(parsed_query.template_position(end)),
+            });
+        }
+        let mut last = 0;
+        for (start, _) in template.match_indices("{:") {
+            let end = match template[start..].find('}') {
+                Some(len) if start >= last => start + len + 1,
+                _ => continue,
+            };
+            let name = &template[start + 2..end - 1];
+            // only the {:name} sequences are placeholders
+            if name.is_empty() || !name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_') {
+                continue;
+            }
+            let replacement = match name {
+                "topic" => table_name.as_str(),
+                "limit" => ":limit",
+                "offset" => ":offset",
+                _ => {
+                    return Err(Error::InvalidQuery {
+                        message: format!(
+                            "Unknown placeholder {{:{}}}, the supported ones are {{:topic}}, {{:limit}} and {{:offset}}",
+                            name
+                        ),
+                        position: Some(parsed_query.template_position(start)),
+                    })
+                }
+            };
+            parsed_query.sql.push_str(&template[last..start]);
+            let sql_start = parsed_query.sql.len();
+            parsed_query.sql.push_str(replacement);
+            parsed_query.placeholders.push(Placeholder {
+                template_start: start,
+                template_end: end,
+                sql_start,
+                sql_end: parsed_query.sql.len(),
+            });
+            last = end;
+        }
+        parsed_query.sql.push_str(&template[last..]);
+        Ok(parsed_query)
+    }
+
+    fn to_error(&self, connection: &Connection, err: rusqlite::Error) -> Error {
+        if err.sqlite_error_code() == Some(ErrorCode::AuthorizationForStatementDenied) {
+            return Error::InvalidQuery {
+                message: "Only SELECT queries on the topic table {:topic} are allowed".into(),
+                position: None,
+            };
+        }
+        // rusqlite doesn't expose the offset of the error in the sql
+        let offset = unsafe { rusqlite::ffi::sqlite3_error_offset(connection.handle()) };
+        Error::InvalidQuery {
+            message: err.to_string(),
+            position: usize::try_from(offset)
+                .ok()
+                .map(|offset| self.sql_to_template_position(offset)),
+        }
+    }
+
+    // map a byte offset in the sql to the char position in the template
+    fn sql_to_template_position(&self, sql_offset: usize) -> usize {
+        let mut offset = sql_offset;
+        for p in &self.placeholders {
+            if sql_offset < p.sql_start {
+                break;
+            }
+            if sql_offset < p.sql_end {
+                offset = p.template_start;
+                break;
+            }
+            offset = sql_offset - p.sql_end + p.template_end;
+        }
+        self.template_position(offset)
+    }
+
+    fn template_position(&self, byte_offset: usize) -> usize {
+        self.template
+            .char_indices()
+            .take_while(|(i, _)| *i < byte_offset)
+            .count()
+    }
+}
+
+// byte offset of the first semicolon outside of literals, quoted identifiers and comments
+fn find_statement_end(sql: &str) -> Option<usize> {
+    let bytes = sql.as_bytes();
+    let mut i = 0;
+    while i < bytes.len() {
+        let closing = match bytes[i] {
+            b';' => return Some(i),
+            b'\'' => "'",
+            b'"' => "\"",
+            b'`' => "`",
+            b'[' => "]",
+            b'-' if bytes.get(i + 1) == Some(&b'-') => "\n",
+            b'/' if bytes.get(i + 1) == Some(&b'*') => "*/",
+            _ => {
+                i += 1;
+                continue;
+            }
+        };
+        // an escaped quote ('') is handled as two consecutive literals
+        i = match sql[i + 1..].find(closing) {
+            Some(len) => i + 1 + len + closing.len(),
+            None => bytes.len(),
+        };
+    }
+    None
+}
+
+// store the record format as plain text to make it easy to query
+impl ToSql for RecordFormat {
+    fn to_sql(&self) -> rusqlite::Result<ToSqlOutput<'_>> {
+        let format = serde_json::to_value(self).map_err(|err| rusqlite::Error::ToSqlConversionFailure(Box::new(err)))?;
+        Ok(ToSqlOutput::from(format.as_str().unwrap_or_default().to_string()))
+    }
+}
+
+impl FromSql for RecordFormat {
+    fn column_result(value: ValueRef<'_>) -> FromSqlResult<Self> {
+        serde_json::from_value(value.as_str()?.into()).map_err(|err| FromSqlError::Other(Box::new(err)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        collections::HashMap,
+        time::{Instant, SystemTime, UNIX_EPOCH},
+    };
+
+    use rusqlite::Connection;
+
+    use crate::lib::{
+        configuration::StoreLimits,
+        consumer::ConsumerOffsetConfiguration,
+        record_store::app_store::Query,
+        types::{ParsedKafkaRecord, RecordFormat, RecordHeader},
+        Error,
+    };
+
+    use super::AppStore;
+
+    #[tokio::test]
+    async fn test_create_table() {
+        let db = AppStore::new();
+        let res = db.create_topic_table("cluster_id_example", "topic_name_example").await;
+        assert!(res.is_ok())
+    }
+
+    #[tokio::test]
+    async fn test_insert_and_get_record() {
+        // arrange
+        let (cluster_id, topic_name) = ("cluster_id_example", "topic_name_example");
+        let db = AppStore::new();
+        db.create_topic_table(&cluster_id, &topic_name)
+            .await
+            .expect("Unable to create the table");
+        let test_record = ParsedKafkaRecord {
+            key_format: Some(RecordFormat::Avro),
+            payload_format: Some(RecordFormat::String),
+            parse_error: Some("Unable to parse the payload".into()),
+            payload_schema_id: Some(12),
+            raw_key: Some("key".into()),
+            raw_payload: Some(vec![0x00, 0x00, 0x00, 0x00, 0x0c, 0xff]),
+            ..get_test_record(topic_name, 0)
+        };
+        // act
+        let res = db
+            .insert_records(cluster_id, topic_name, std::slice::from_ref(&test_record))
+            .await;
+        let records_back = db.get_records(cluster_id, topic_name, 0, 1000).await.unwrap();
+        let raw_records_back = db
+            .query_records(&Query {
+                cluster_id: cluster_id.into(),
+                topic_name: topic_name.into(),
+                limit: -1,
+                offset: 0,
+                query_template: "SELECT * FROM {:topic}".into(),
+            })
+            .await
+            .unwrap();
+        // assert
+        assert!(res.is_ok());
+        assert!(records_back.len() == 1);
+        // the raw key and payload are only read when selected
+        assert_eq!(
+            records_back[0],
+            ParsedKafkaRecord {
+                raw_key: None,
+                raw_payload: None,
+                ..test_record.clone()
+            }
+        );
+        assert_eq!(raw_records_back, vec![test_record]);
+    }
+
+    #[tokio::test]
+    async fn test_get_size() {
+        // arrange
+        let (cluster_id, topic_name) = ("cluster_id_example", "topic_name_example");
+        let db = AppStore::new();
+        db.create_topic_table(&cluster_id, &topic_name)
+            .await
+            .expect("Unable to create the table");
+        // act
+        for offset in 0..3 {
+            db.insert_records(cluster_id, topic_name, &[get_test_record(topic_name, offset)])
+                .await
+                .unwrap();
+        }
+        let table_size = db.get_size(cluster_id, topic_name).await.unwrap();
+        // assert
+        assert_eq!(table_size, 3);
+    }
+
+    #[tokio::test]
+    async fn test_get_size_with_query() {
+        // arrange
+        let (cluster_id, topic_name) = ("cluster_id_example", "topic_name_example");
+        let db = AppStore::new();
+        db.create_topic_table(&cluster_id, &topic_name)
+            .await
+            .expect("Unable to create the table");
+        // act
+        db.insert_records(cluster_id, topic_name, &[get_test_record(topic_name, 1)])
+            .await
+            .unwrap();
+        db.insert_records(cluster_id, topic_name, &[get_test_record(topic_name, 0)])
+            .await
+            .unwrap();
+        let other_partition = ParsedKafkaRecord {
+            partition: 3,
+            ..get_test_record(topic_name, 0)
+        };
+        db.insert_records(cluster_id, topic_name, &[other_partition])
+            .await
+            .unwrap();
         let table_size = db
             .get_size_with_query(&Query {
                 cluster_id: cluster_id.into(),
@@ -241,8 +1470,125 @@ mod tests {
             })
             .await
             .unwrap();
+        let size_with_comment = db
+            .get_size_with_query(&Query {
+                cluster_id: cluster_id.into(),
+                topic_name: topic_name.into(),
+                limit: -1,
+                offset: 0,
+                query_template: "SELECT * from {:topic} WHERE offset = 0 -- first records".into(),
+            })
+            .await;
         // assert
         assert_eq!(table_size, 2);
+        assert_eq!(size_with_comment.unwrap(), 2);
+    }
+
+    #[tokio::test]
+    async fn test_query_headers() {
+        // arrange
+        let (cluster_id, topic_name) = ("cluster_id_example", "topic_name_example");
+        let db = AppStore::new();
+        db.create_topic_table(cluster_id, topic_name)
+            .await
+            .expect("Unable to create the table");
+        for offset in 0..3 {
+            db.insert_records(cluster_id, topic_name, &[get_test_record(topic_name, offset)])
+                .await
+                .unwrap();
+        }
+        // act
+        let records = db
+            .query_records(&Query {
+                cluster_id: cluster_id.into(),
+                topic_name: topic_name.into(),
+                limit: -1,
+                offset: -1,
+                query_template: "SELECT partition, offset, timestamp, key, payload, headers FROM {:topic} WHERE EXISTS (SELECT 1 FROM json_each(headers) WHERE json_extract(value, '$.key') = 'trace-id' AND json_extract(value, '$.value') = 'trace-1')".into(),
+            })
+            .await
+            .unwrap();
+        let records_without_headers = db
+            .query_records(&Query {
+                cluster_id: cluster_id.into(),
+                topic_name: topic_name.into(),
+                limit: -1,
+                offset: -1,
+                query_template: "SELECT partition, offset, timestamp, key, payload FROM {:topic}".into(),
+            })
+            .await
+            .unwrap();
+        // assert
+        assert_eq!(records, vec![get_test_record(topic_name, 1)]);
+        assert_eq!(records_without_headers.len(), 3);
+        assert!(records_without_headers[0].headers.is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_query_validation_errors() {
+        // arrange
+        let (cluster_id, topic_name) = ("cluster_id_example", "topic_name_example");
+        let db = AppStore::new();
+        db.create_topic_table(cluster_id, topic_name)
+            .await
+            .expect("Unable to create the table");
+        let invalid_record = ParsedKafkaRecord {
+            validation_errors: Some(vec!["/age: \"42\" is not of type \"integer\"".into()]),
+            ..get_test_record(topic_name, 1)
+        };
+        db.insert_records(cluster_id, topic_name, &[get_test_record(topic_name, 0)])
+            .await
+            .unwrap();
+        db.insert_records(cluster_id, topic_name, std::slice::from_ref(&invalid_record))
+            .await
+            .unwrap();
+        // act
+        let records = db
+            .query_records(&Query {
+                cluster_id: cluster_id.into(),
+                topic_name: topic_name.into(),
+                limit: -1,
+                offset: -1,
+                query_template: "SELECT partition, offset, timestamp, key, payload, headers, validation_errors FROM {:topic} WHERE json_array_length(validation_errors) > 0".into(),
+            })
+            .await
+            .unwrap();
+        // assert
+        assert_eq!(records, vec![invalid_record]);
+    }
+
+    #[tokio::test]
+    async fn test_query_parse_errors() {
+        // arrange
+        let (cluster_id, topic_name) = ("cluster_id_example", "topic_name_example");
+        let db = AppStore::new();
+        db.create_topic_table(cluster_id, topic_name)
+            .await
+            .expect("Unable to create the table");
+        let failed_record = ParsedKafkaRecord {
+            parse_error: Some("Unable to parse the payload: invalid avro".into()),
+            raw_payload: Some(vec![0x00, 0x00, 0x00, 0x00, 0x01, 0xff]),
+            ..get_test_record(topic_name, 1)
+        };
+        db.insert_records(cluster_id, topic_name, &[get_test_record(topic_name, 0)])
+            .await
+            .unwrap();
+        db.insert_records(cluster_id, topic_name, std::slice::from_ref(&failed_record))
+            .await
+            .unwrap();
+        // act
+        let records = db
+            .query_records(&Query {
+                cluster_id: cluster_id.into(),
+                topic_name: topic_name.into(),
+                limit: -1,
+                offset: -1,
+                query_template: "SELECT partition, offset, timestamp, key, payload, headers, parse_error, raw_payload FROM {:topic} WHERE parse_error IS NOT NULL".into(),
+            })
+            .await
+            .unwrap();
+        // assert
+        assert_eq!(records, vec![failed_record]);
     }
 
     #[tokio::test]
@@ -253,11 +1599,12 @@ mod tests {
         db.create_topic_table(&cluster_id, &topic_name)
             .await
             .expect("Unable to create the table");
-        let test_record = get_test_record(topic_name, 0);
         // act
-        db.insert_record(cluster_id, topic_name, &test_record).await.unwrap();
-        db.insert_record(cluster_id, topic_name, &test_record).await.unwrap();
-        db.insert_record(cluster_id, topic_name, &test_record).await.unwrap();
+        for offset in 0..3 {
+            db.insert_records(cluster_id, topic_name, &[get_test_record(topic_name, offset)])
+                .await
+                .unwrap();
+        }
         let first_1000_res = db.get_records(cluster_id, topic_name, 0, 1000).await.unwrap();
         let first_res = db.get_records(cluster_id, topic_name, 1, 1).await.unwrap();
         let no_res = db.get_records(cluster_id, topic_name, 3, 1000).await.unwrap();
@@ -267,6 +1614,509 @@ mod tests {
         assert_eq!(no_res.len(), 0);
     }
 
+    #[tokio::test]
+    async fn test_only_read_queries_on_the_topic_table() {
+        // arrange
+        let (cluster_id, topic_name) = ("cluster_id_example", "topic_name_example");
+        let db = AppStore::new();
+        db.create_topic_table(cluster_id, topic_name).await.unwrap();
+        db.create_topic_table(cluster_id, "another_topic").await.unwrap();
+        db.insert_records(cluster_id, topic_name, &[get_test_record(topic_name, 0)])
+            .await
+            .unwrap();
+        let query = |query_template: &str| Query {
+            cluster_id: cluster_id.into(),
+            topic_name: topic_name.into(),
+            limit: 10,
+            offset: 0,
+            query_template: query_template.into(),
+        };
+        // act
+        for template in [
+            "DELETE FROM {:topic}",
+            "DROP TABLE {:topic}",
+            "SELECT * FROM '[cluster_id_example].[another_topic]'",
+            "SELECT * FROM sqlite_master",
+            "SELECT * FROM {:topic}; DROP TABLE {:topic}",
+        ] {
+            let res = db.query_records(&query(template)).await;
+            // assert
+            assert!(
+                matches!(res, Err(Error::InvalidQuery { .. })),
+                "{} must be invalid",
+                template
+            );
+        }
+        // semicolons in literals and comments are allowed
+        assert!(db
+            .query_records(&query("SELECT * FROM {:topic} WHERE key != 'a;b' -- comment;\n;"))
+            .await
+            .is_ok());
+        assert_eq!(db.get_size(cluster_id, topic_name).await.unwrap(), 1);
+        assert!(db.get_size(cluster_id, "another_topic").await.is_ok());
+    }
+
+    #[tokio::test]
+    async fn test_stream_records_in_chunks() {
+        // arrange
+        let (cluster_id, topic_name) = ("cluster_id_example", "topic_name_example");
+        let db = AppStore::new();
+        db.create_topic_table(cluster_id, topic_name).await.unwrap();
+        let records: Vec<_> = (0..2500).map(|offset| get_test_record(topic_name, offset)).collect();
+        db.insert_records(cluster_id, topic_name, &records).await.unwrap();
+        let query = Query {
+            cluster_id: cluster_id.into(),
+            topic_name: topic_name.into(),
+            limit: -1,
+            offset: 0,
+            query_template: "SELECT * FROM {:topic} ORDER BY offset desc".into(),
+        };
+        // act
+        let mut chunks = vec![];
+        let mut offsets = vec![];
+        db.stream_records(&query, |records| {
+            // the store can be used while the chunk is handled
+            assert!(db.conn.try_lock().is_some());
+            chunks.push(records.len());
+            offsets.extend(records.into_iter().map(|r| r.offset));
+            Ok(())
+        })
+        .unwrap();
+        // assert
+        assert_eq!(chunks, vec![1000, 1000, 500]);
+        assert_eq!(offsets, (0..2500).rev().collect::<Vec<_>>());
+        let temp_tables: i64 = db
+            .conn
+            .lock()
+            .query_row("SELECT count(*) FROM sqlite_temp_master", [], |row| row.get(0))
+            .unwrap();
+        assert_eq!(temp_tables, 0);
+    }
+
+    #[tokio::test]
+    async fn test_query_error_position() {
+        // arrange
+        let (cluster_id, topic_name) = ("cluster_id_example", "topic_name_example");
+        let db = AppStore::new();
+        db.create_topic_table(cluster_id, topic_name).await.unwrap();
+        let query = |query_template: &str| Query {
+            cluster_id: cluster_id.into(),
+            topic_name: topic_name.into(),
+            limit: 10,
+            offset: 0,
+            query_template: query_template.into(),
+        };
+        // act
+        let syntax_error = db
+            .validate_query(&query("SELECT * FROM {:topic} WHER offset > 0 LIMIT {:limit}"))
+            .await;
+        let unknown_placeholder = db.query_records(&query("SELECT * FROM {:table}")).await;
+        let valid = db
+            .query_records(&query("SELECT * FROM {:topic} LIMIT {:limit} OFFSET {:offset};"))
+            .await;
+        // assert
+        // WHER is parsed as the table alias, the error is near offset
+        match syntax_error {
+            Err(Error::InvalidQuery { position, .. }) => assert_eq!(position, Some(28)),
+            _ => panic!("the query must be invalid"),
+        }
+        match unknown_placeholder {
+            Err(Error::InvalidQuery { position, .. }) => assert_eq!(position, Some(14)),
+            _ => panic!("the query must be invalid"),
+        }
+        assert!(valid.is_ok());
+    }
+
+    #[tokio::test]
+    async fn test_reopen_file_store() {
+        // arrange
+        let (cluster_id, topic_name) = ("cluster_id_example", "topic_name_example");
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("store").join("records.db");
+        {
+            let db = AppStore::from_path(&path).unwrap();
+            db.create_topic_table(cluster_id, topic_name).await.unwrap();
+            db.insert_records(cluster_id, topic_name, &[get_test_record(topic_name, 0)])
+                .await
+                .unwrap();
+        }
+        // act
+        let db = AppStore::from_path(&path).unwrap();
+        db.create_topic_table(cluster_id, topic_name).await.unwrap();
+        let records = db.get_records(cluster_id, topic_name, 0, 1000).await.unwrap();
+        // assert
+        assert_eq!(records, vec![get_test_record(topic_name, 0)]);
+    }
+
+    #[tokio::test]
+    async fn test_migrate_file_store() {
+        // arrange
+        let (cluster_id, topic_name) = ("cluster_id_example", "topic_name_example");
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("records.db");
+        {
+            // a store of a previous version, with duplicated offsets and the headers stored as an object
+            let conn = Connection::open(&path).unwrap();
+            conn.execute_batch(
+                r#"CREATE TABLE '[cluster_id_example].[topic_name_example]' (
+                    partition NUMBER, offset NUMBER, timestamp NUMBER, key TEXT, payload TEXT, headers TEXT,
+                    key_format TEXT, payload_format TEXT, validation_errors TEXT, parse_error TEXT,
+                    key_schema_id NUMBER, payload_schema_id NUMBER, raw_key BLOB, raw_payload BLOB);
+                INSERT INTO '[cluster_id_example].[topic_name_example]'
+                    (partition, offset, timestamp, key, payload, headers)
+                VALUES (2, 0, 321123321, 'key', 'example payload', '{"trace-id":"trace-0"}'),
+                    (2, 0, 321123321, 'key', 'example payload', '{"trace-id":"trace-0"}');"#,
+            )
+            .unwrap();
+        }
+        // act
+        let db = AppStore::from_path(&path).unwrap();
+        db.create_topic_table(cluster_id, topic_name).await.unwrap();
+        let records = db.get_records(cluster_id, topic_name, 0, 1000).await.unwrap();
+        let version: i64 = db
+            .conn
+            .lock()
+            .query_row("PRAGMA user_version", [], |row| row.get(0))
+            .unwrap();
+        // assert
+        assert_eq!(records, vec![get_test_record(topic_name, 0)]);
+        assert_eq!(version, 2);
+        assert_eq!(db.get_size(cluster_id, topic_name).await.unwrap(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_list_and_delete_sessions() {
+        // arrange
+        let (cluster_id, topic_name) = ("cluster_id_example", "topic_name_example");
+        let db = AppStore::new();
+        db.create_topic_table(cluster_id, topic_name).await.unwrap();
+        db.save_session(cluster_id, topic_name, &ConsumerOffsetConfiguration::End, 1000)
+            .await
+            .unwrap();
+        db.save_session(cluster_id, "not_started", &ConsumerOffsetConfiguration::Beginning, 2000)
+            .await
+            .unwrap();
+        for offset in 0..3 {
+            let record = ParsedKafkaRecord {
+                timestamp: Some(100 + offset),
+                ..get_test_record(topic_name, offset)
+            };
+            db.insert_records(cluster_id, topic_name, &[record]).await.unwrap();
+        }
+        // act
+        let sessions = db.list_sessions().await.unwrap();
+        db.delete_session(cluster_id, topic_name).await.unwrap();
+        let sessions_after_delete = db.list_sessions().await.unwrap();
+        // assert
+        assert_eq!(sessions.len(), 2);
+        assert_eq!(sessions[0].topic_name, "not_started");
+        assert_eq!(sessions[0].record_count, 0);
+        assert_eq!(sessions[1].topic_name, topic_name);
+        assert!(matches!(sessions[1].offset_config, ConsumerOffsetConfiguration::End));
+        assert_eq!(sessions[1].started_at, 1000);
+        assert_eq!(sessions[1].record_count, 3);
+        assert_eq!(
+            (sessions[1].min_timestamp, sessions[1].max_timestamp),
+            (Some(100), Some(102))
+        );
+        assert_eq!(sessions_after_delete.len(), 1);
+        assert!(db.get_records(cluster_id, topic_name, 0, 1000).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_payload_projections() {
+        // arrange
+        let (cluster_id, topic_name) = ("cluster_id_example", "topic_name_example");
+        let db = AppStore::new();
+        let projections = HashMap::from([
+            ("amount".to_string(), "$.amount".to_string()),
+            ("user_name".into(), "$.user.name".into()),
+        ]);
+        db.set_projections(cluster_id, topic_name, &projections).await.unwrap();
+        for (offset, payload) in [
+            r#"{"amount":5,"user":{"name":"ann"}}"#,
+            "not a json",
+            r#"{"amount":20.5}"#,
+        ]
+        .iter()
+        .enumerate()
+        {
+            let record = ParsedKafkaRecord {
+                payload: Some(payload.to_string()),
+                raw_payload: Some(payload.as_bytes().to_vec()),
+                ..get_test_record(topic_name, offset as i64)
+            };
+            db.insert_records(cluster_id, topic_name, &[record]).await.unwrap();
+        }
+        let query = |template: &str| Query {
+            cluster_id: cluster_id.into(),
+            topic_name: topic_name.into(),
+            limit: -1,
+            offset: 0,
+            query_template: template.into(),
+        };
+        // act
+        let all = db.get_records(cluster_id, topic_name, 0, 10).await.unwrap();
+        let filtered = db
+            .query_records(&query(
+                "SELECT partition, offset, timestamp, key, payload, amount FROM {:topic} WHERE amount > 1 ORDER BY amount desc",
+            ))
+            .await
+            .unwrap();
+        // changing a path replaces the column, removing a projection drops it
+        db.set_projections(
+            cluster_id,
+            topic_name,
+            &HashMap::from([("amount".to_string(), "$.user.name".to_string())]),
+        )
+        .await
+        .unwrap();
+        let updated = db.get_records(cluster_id, topic_name, 0, 10).await.unwrap();
+        // assert
+        let ann = all.iter().find(|r| r.offset == 0).unwrap();
+        assert_eq!(ann.projections["amount"], serde_json::json!(5));
+        assert_eq!(ann.projections["user_name"], serde_json::json!("ann"));
+        let not_json = all.iter().find(|r| r.offset == 1).unwrap();
+        assert_eq!(not_json.projections["amount"], serde_json::Value::Null);
+        // the default query doesn't read the raw payloads
+        assert!(all.iter().all(|r| r.raw_payload.is_none()));
+        assert_eq!(filtered.iter().map(|r| r.offset).collect::<Vec<_>>(), vec![2, 0]);
+        assert_eq!(
+            filtered[0].projections,
+            HashMap::from([("amount".into(), serde_json::json!(20.5))])
+        );
+        assert_eq!(updated[0].projections.len(), 1);
+        assert_eq!(
+            updated.iter().find(|r| r.offset == 0).unwrap().projections["amount"],
+            serde_json::json!("ann")
+        );
+    }
+
+    #[tokio::test]
+    async fn test_invalid_projections() {
+        let db = AppStore::new();
+        for (name, path) in [("payload", "$.a"), ("a b", "$.a"), ("1a", "$.a"), ("a", "a.b")] {
+            let projections = HashMap::from([(name.to_string(), path.to_string())]);
+            let res = db.set_projections("cluster_id_example", "topic", &projections).await;
+            assert!(matches!(res, Err(Error::SqlError { .. })), "{} {}", name, path);
+        }
+    }
+
+    #[tokio::test]
+    async fn test_search_records() {
+        // arrange
+        let (cluster_id, topic_name) = ("cluster_id_example", "topic_name_example");
+        let db = AppStore::new();
+        db.create_topic_table(cluster_id, topic_name).await.unwrap();
+        let payloads = [
+            r#"{"order":"ord-123","status":"shipped to the customer after a long wait"}"#,
+            r#"{"order":"ord-456","note":"order order"}"#,
+            "plain text payload",
+        ];
+        for (offset, payload) in payloads.iter().enumerate() {
+            let record = ParsedKafkaRecord {
+                payload: Some(payload.to_string()),
+                ..get_test_record(topic_name, offset as i64)
+            };
+            db.insert_records(cluster_id, topic_name, &[record]).await.unwrap();
+        }
+        let search = |text: &'static str| db.search_records(cluster_id, topic_name, text, 0, 10);
+        // act
+        let by_payload = search("ord-123").await.unwrap();
+        let by_header = search("trace-2").await.unwrap();
+        let ranked = search("order").await.unwrap();
+        let all_words = search("plain payload").await.unwrap();
+        let fts_syntax = search("\"ord OR NEAR(").await.unwrap();
+        db.clear(cluster_id, topic_name).await.unwrap();
+        let after_clear = search("ord-123").await.unwrap();
+        // assert
+        let offsets = |records: Vec<ParsedKafkaRecord>| records.iter().map(|r| r.offset).collect::<Vec<_>>();
+        assert_eq!(offsets(by_payload), vec![0]);
+        assert_eq!(offsets(by_header), vec![2]);
+        assert_eq!(offsets(ranked), vec![1, 0]);
+        assert_eq!(offsets(all_words), vec![2]);
+        assert!(fts_syntax.is_empty());
+        assert!(after_clear.is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_drop_legacy_search_triggers() {
+        // arrange
+        let (cluster_id, topic_name) = ("cluster_id_example", "topic_name_example");
+        let db = AppStore::new();
+        db.create_topic_table(cluster_id, topic_name).await.unwrap();
+        // the triggers created by the previous versions of the search index
+        let index_name = AppStore::get_search_index_name(cluster_id, topic_name);
+        let (table, index) = (
+            AppStore::get_table_name(cluster_id, topic_name),
+            AppStore::quote_identifier(&index_name),
+        );
+        db.conn
+            .lock()
+            .execute_batch(
+                format!(
+                    "CREATE TRIGGER {} AFTER INSERT ON {table} BEGIN
+                        INSERT INTO {index} (rowid, key, payload, headers) VALUES (new.rowid, new.key, new.payload, '');
+                    END;
+                    CREATE TRIGGER {} AFTER DELETE ON {table} BEGIN
+                        DELETE FROM {index} WHERE rowid = old.rowid;
+                    END;",
+                    AppStore::quote_identifier(&format!("{}_insert", index_name)),
+                    AppStore::quote_identifier(&format!("{}_delete", index_name)),
+                )
+                .as_str(),
+            )
+            .unwrap();
+        // act
+        db.create_topic_table(cluster_id, topic_name).await.unwrap();
+        db.insert_records(cluster_id, topic_name, &[get_test_record(topic_name, 0)])
+            .await
+            .unwrap();
+        // assert
+        let triggers: i64 = db
+            .conn
+            .lock()
+            .query_row("SELECT count(*) FROM sqlite_master WHERE type = 'trigger'", [], |row| {
+                row.get(0)
+            })
+            .unwrap();
+        assert_eq!(triggers, 0);
+        let found = db
+            .search_records(cluster_id, topic_name, "trace-0", 0, 10)
+            .await
+            .unwrap();
+        assert_eq!(found.len(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_skip_stored_records() {
+        // arrange
+        let (cluster_id, topic_name) = ("cluster_id_example", "topic_name_example");
+        let db = AppStore::new();
+        db.create_topic_table(cluster_id, topic_name).await.unwrap();
+        let first: Vec<_> = (0..3).map(|offset| get_test_record(topic_name, offset)).collect();
+        let mut overlapping: Vec<_> = (2..5).map(|offset| get_test_record(topic_name, offset)).collect();
+        overlapping.push(ParsedKafkaRecord {
+            partition: 3,
+            ..get_test_record(topic_name, 0)
+        });
+        // act
+        db.insert_records(cluster_id, topic_name, &first).await.unwrap();
+        db.insert_records(cluster_id, topic_name, &overlapping).await.unwrap();
+        // assert
+        assert_eq!(db.get_size(cluster_id, topic_name).await.unwrap(), 6);
+        let indexed = db
+            .search_records(cluster_id, topic_name, "example", 0, 10)
+            .await
+            .unwrap();
+        assert_eq!(indexed.len(), 6);
+        assert_eq!(
+            db.get_last_offsets(cluster_id, topic_name).await.unwrap(),
+            HashMap::from([(2, 4), (3, 0)])
+        );
+    }
+
+    #[tokio::test]
+    async fn test_evict_records() {
+        // arrange
+        let (cluster_id, topic_name, other_topic) = ("cluster_id_example", "topic_name_example", "other_topic_example");
+        // each test record is 56 bytes
+        let db = AppStore::new().with_limits(
+            StoreLimits {
+                max_records: Some(3),
+                ..Default::default()
+            },
+            StoreLimits {
+                max_bytes: Some(280),
+                max_age: Some(60 * 60 * 1000),
+                ..Default::default()
+            },
+        );
+        let now = SystemTime::now().duration_since(UNIX_EPOCH).unwrap().as_millis() as i64;
+        let get_record = |topic_name: &str, offset: i64, timestamp: i64| ParsedKafkaRecord {
+            timestamp: Some(timestamp),
+            ..get_test_record(topic_name, offset)
+        };
+        for topic_name in [topic_name, other_topic] {
+            db.create_topic_table(cluster_id, topic_name).await.unwrap();
+        }
+        let other_records: Vec<_> = (0..4).map(|offset| get_record(other_topic, offset, now)).collect();
+        db.insert_records(cluster_id, other_topic, &other_records)
+            .await
+            .unwrap();
+        let mut records = vec![get_record(topic_name, 0, 0)];
+        records.extend((1..5).map(|offset| get_record(topic_name, offset, now)));
+        db.insert_records(cluster_id, topic_name, &records).await.unwrap();
+        // act
+        let evicted = db.evict_records(cluster_id, topic_name).await.unwrap();
+        // assert
+        let offsets = |records: Vec<ParsedKafkaRecord>| {
+            let mut offsets: Vec<_> = records.iter().map(|r| r.offset).collect();
+            offsets.sort();
+            offsets
+        };
+        // the old record, then the oldest records over the topic limit and over the global limit
+        assert_eq!(evicted, 4);
+        let topic_records = db.get_records(cluster_id, topic_name, 0, 10).await.unwrap();
+        assert_eq!(offsets(topic_records), vec![2, 3, 4]);
+        let other_records = db.get_records(cluster_id, other_topic, 0, 10).await.unwrap();
+        assert_eq!(offsets(other_records), vec![2, 3]);
+        let indexed = db
+            .search_records(cluster_id, topic_name, "example", 0, 10)
+            .await
+            .unwrap();
+        assert_eq!(offsets(indexed), vec![2, 3, 4]);
+        assert_eq!(db.get_evicted_count(cluster_id, topic_name).await.unwrap(), 2);
+        assert_eq!(db.get_evicted_count(cluster_id, other_topic).await.unwrap(), 2);
+        // nothing else to evict
+        assert_eq!(db.evict_records(cluster_id, topic_name).await.unwrap(), 0);
+        db.clear(cluster_id, topic_name).await.unwrap();
+        assert_eq!(db.get_evicted_count(cluster_id, topic_name).await.unwrap(), 0);
+    }
+
+    // compare the throughput of one transaction per record with the batches used by the consumer
+    // run with: cargo test --release benchmark_insert_throughput -- --ignored --nocapture
+    #[tokio::test]
+    #[ignore = "benchmark"]
+    async fn benchmark_insert_throughput() {
+        // arrange
+        const RECORDS: usize = 20_000;
+        const BATCH_SIZE: usize = 500;
+        let (cluster_id, topic_name) = ("cluster_id_example", "topic_name_example");
+        let dir = tempfile::tempdir().unwrap();
+        let db = AppStore::from_path(&dir.path().join("records.db")).unwrap();
+        db.create_topic_table(cluster_id, topic_name).await.unwrap();
+        let records: Vec<_> = (0..RECORDS)
+            .map(|offset| get_test_record(topic_name, offset as i64))
+            .collect();
+        // act
+        let start = Instant::now();
+        for record in &records {
+            db.insert_records(cluster_id, topic_name, std::slice::from_ref(record))
+                .await
+                .unwrap();
+        }
+        let single = start.elapsed();
+        db.clear(cluster_id, topic_name).await.unwrap();
+        let start = Instant::now();
+        for batch in records.chunks(BATCH_SIZE) {
+            db.insert_records(cluster_id, topic_name, batch).await.unwrap();
+        }
+        let batched = start.elapsed();
+        // assert
+        println!(
+            "1 record per transaction: {:.0} records/s",
+            RECORDS as f64 / single.as_secs_f64()
+        );
+        println!(
+            "{} records per transaction: {:.0} records/s",
+            BATCH_SIZE,
+            RECORDS as f64 / batched.as_secs_f64()
+        );
+        assert_eq!(db.get_size(cluster_id, topic_name).await.unwrap(), RECORDS);
+        assert!(batched < single);
+    }
+
     fn get_test_record(topic_name: &str, offset: i64) -> ParsedKafkaRecord {
         ParsedKafkaRecord {
             payload: Some("example payload".to_string()),
@@ -275,6 +2125,20 @@ mod tests {
             timestamp: Some(321123321),
             partition: 2,
             offset,
+            headers: vec![RecordHeader {
+                key: "trace-id".into(),
+                value: Some(format!("trace-{}", offset)),
+                binary: false,
+            }],
+            key_format: None,
+            payload_format: None,
+            validation_errors: None,
+            parse_error: None,
+            key_schema_id: None,
+            payload_schema_id: None,
+            raw_key: None,
+            raw_payload: None,
+            projections: HashMap::new(),
         }
     }
 }
@@ -0,0 +1,438 @@
+use std::{
+    collections::HashMap,
+    fs::File,
+    io::{BufWriter, Cursor, Write},
+    path::Path,
+    sync::Arc,
+};
+
+use apache_avro::{from_avro_datum, Schema, Writer};
+use serde::{Deserialize, Serialize};
+
+use crate::lib::{
+    error::{Error, Result},
+    schema_registry::{CachedSchemaRegistry, SchemaRegistryClient},
+    types::ParsedKafkaRecord,
+};
+
+use super::TopicStore;
+
+// number of records exported between two progress notifications
+const EXPORT_PROGRESS_INTERVAL: usize = 1000;
+// all the columns, the avro export writes the raw payloads
+const DEFAULT_EXPORT_TEMPLATE: &str = "SELECT * FROM {:topic} ORDER BY timestamp desc";
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub enum ExportFormat {
+    Jsonl,
+    Csv,
+    // avro object container file, the payloads are written with the schema of the first record
+    Avro,
+}
+
+// schema of the container file and writer schemas of all the exported records
+struct ExportSchemas {
+    schema_id: i32,
+    schemas: HashMap<i32, Schema>,
+}
+
+pub struct RecordExporter<C = CachedSchemaRegistry>
+where
+    C: SchemaRegistryClient + Send + Sync,
+{
+    schema_registry_client: Option<Arc<C>>,
+}
+
+impl<C> RecordExporter<C>
+where
+    C: SchemaRegistryClient + Send + Sync,
+{
+    pub fn new(schema_registry_client: Option<Arc<C>>) -> Self {
+        RecordExporter { schema_registry_client }
+    }
+
+    // write the records returned by the query to the output file and
+    // call on_progress with the number of records exported so far
+    pub async fn export(
+        &self,
+        topic_store: Arc<TopicStore>,
+        query: Option<&str>,
+        format: &ExportFormat,
+        output_path: &Path,
+        on_progress: impl Fn(usize) + Send + 'static,
+    ) -> Result<usize> {
+        // report the errors in the user query before wrapping it
+        if let Some(query) = query {
+            topic_store.validate_query(query).await?;
+        }
+        let template = get_export_template(query);
+        // the schemas are retrieved upfront, so the records can be written in a blocking task
+        let export_schemas = match format {
+            ExportFormat::Avro => Some(self.get_export_schemas(&topic_store, &template).await?),
+            _ => None,
+        };
+        let (format, output_path) = (format.clone(), output_path.to_path_buf());
+        tauri::async_runtime::spawn_blocking(move || {
+            let file = BufWriter::new(File::create(output_path)?);
+            let mut writer = match (&format, &export_schemas) {
+                (ExportFormat::Avro, Some(export_schemas)) => ExportWriter::avro(export_schemas, file),
+                (ExportFormat::Csv, _) => ExportWriter::csv(file)?,
+                _ => ExportWriter::Jsonl(file),
+            };
+            let mut count = 0;
+            topic_store.stream_records(&template, |records| {
+                for record in records {
+                    writer.write(&record)?;
+                    count += 1;
+                    if count % EXPORT_PROGRESS_INTERVAL == 0 {
+                        on_progress(count);
+                    }
+                }
+                Ok(())
+            })?;
+            writer.finish()?;
+            on_progress(count);
+            Ok(count)
+        })
+        .await
+        .map_err(|err| Error::IO {
+            message: format!("Unable to complete the export\n{}", err),
+        })?
+    }
+
+    // the container file schema is the writer schema of the first exported record,
+    // the records produced with a different schema version are resolved to it
+    async fn get_export_schemas(&self, topic_store: &TopicStore, template: &str) -> Result<ExportSchemas> {
+        let first = topic_store
+            .get_records(Some(&format!("SELECT * FROM ({}\n) LIMIT 1", template)), 0, -1)
+            .await?;
+        let schema_id = first
+            .first()
+            .and_then(|r| r.payload_schema_id)
+            .ok_or_else(|| Error::AvroParse {
+                message: "Unable to export to avro: the first record has no payload schema id. Make sure the query selects the payload_schema_id and raw_payload columns".into(),
+            })?;
+        let mut schemas = HashMap::new();
+        for id in topic_store.get_payload_schema_ids(template).await? {
+            schemas.insert(id, self.get_schema(id).await?);
+        }
+        Ok(ExportSchemas { schema_id, schemas })
+    }
+
+    async fn get_schema(&self, id: i32) -> Result<Schema> {
+        let client = self.schema_registry_client.as_ref().ok_or_else(|| Error::AvroParse {
+            message: "Unable to export to avro: missing schema registry configuration".into(),
+        })?;
+        client.get_schema_by_id(id).await.map_err(|err| Error::AvroParse {
+            message: format!(
+                "Unable to retrieve the schema {} from schema registry\n{}",
+                id,
+                err.to_string()
+            ),
+        })
+    }
+}
+
+enum ExportWriter<'a, W: Write> {
+    Jsonl(W),
+    Csv(W),
+    Avro {
+        schemas: &'a ExportSchemas,
+        writer: Writer<'a, W>,
+    },
+}
+
+impl<'a, W: Write> ExportWriter<'a, W> {
+    fn csv(mut out: W) -> Result<Self> {
+        writeln!(out, "partition,offset,timestamp,key,payload,headers")?;
+        Ok(ExportWriter::Csv(out))
+    }
+
+    fn avro(schemas: &'a ExportSchemas, out: W) -> Self {
+        ExportWriter::Avro {
+            schemas,
+            writer: Writer::new(&schemas.schemas[&schemas.schema_id], out),
+        }
+    }
+
+    fn write(&mut self, record: &ParsedKafkaRecord) -> Result<()> {
+        match self {
+            ExportWriter::Jsonl(out) => writeln!(out, "{}", serde_json::to_string(record)?)?,
+            ExportWriter::Csv(out) => writeln!(
+                out,
+                "{},{},{},{},{},{}",
+                record.partition,
+                record.offset,
+                record.timestamp.map(|t| t.to_string()).unwrap_or_default(),
+                to_csv_field(record.key.as_deref().unwrap_or_default()),
+                to_csv_field(record.payload.as_deref().unwrap_or_default()),
+                to_csv_field(&serde_json::to_string(&record.headers)?),
+            )?,
+            ExportWriter::Avro { schemas, writer } => {
+                let map_err = |message: String| Error::AvroParse {
+                    message: format!(
+                        "Unable to export the record at offset {} of partition {}\n{}",
+                        record.offset, record.partition, message
+                    ),
+                };
+                let (id, raw) = match (record.payload_schema_id, &record.raw_payload) {
+                    (Some(id), Some(raw)) if raw.len() > 5 => (id, raw),
+                    _ => return Err(map_err("Not an avro record".into())),
+                };
+                let writer_schema = schemas
+                    .schemas
+                    .get(&id)
+                    .ok_or_else(|| map_err(format!("Missing writer schema {}", id)))?;
+                // records produced with a different schema are resolved to the export schema
+                let reader_schema = (id != schemas.schema_id).then(|| &schemas.schemas[&schemas.schema_id]);
+                let value = from_avro_datum(writer_schema, &mut Cursor::new(&raw[5..]), reader_schema)
+                    .map_err(|err| map_err(err.to_string()))?;
+                writer.append(value).map_err(|err| map_err(err.to_string()))?;
+            }
+        }
+        Ok(())
+    }
+
+    fn finish(self) -> Result<()> {
+        match self {
+            ExportWriter::Jsonl(mut out) | ExportWriter::Csv(mut out) => out.flush()?,
+            ExportWriter::Avro { writer, .. } => {
+                writer
+                    .into_inner()
+                    .map_err(|err| Error::AvroParse {
+                        message: format!("Unable to write the avro container file\n{}", err),
+                    })?
+                    .flush()?;
+            }
+        }
+        Ok(())
+    }
+}
+
+// the user query is exported with a single statement, its limit and offset are ignored
+fn get_export_template(query: Option<&str>) -> String {
+    query
+        .unwrap_or(DEFAULT_EXPORT_TEMPLATE)
+        .trim()
+        .trim_end_matches(';')
+        .into()
+}
+
+fn to_csv_field(value: &str) -> String {
+    if value.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", value.replace('"', "\"\""))
+    } else {
+        value.into()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        collections::HashMap,
+        fs::{self, File},
+        sync::{Arc, Mutex},
+    };
+
+    use apache_avro::{to_avro_datum, types::Value as AvroValue, Reader, Schema as ApacheAvroSchema};
+    use async_trait::async_trait;
+
+    use crate::lib::{
+        parser::RecordParser,
+        record_store::{AppStore, TopicStore},
+        schema_registry::{RawSchema, Result, Schema, SchemaRegistryClient, Subject},
+        types::ParsedKafkaRecord,
+    };
+
+    use super::{get_export_template, to_csv_field, ExportFormat, RecordExporter};
+
+    const SCHEMA_V1: &str = r#"{"type": "record", "name": "sample", "fields": [{"name": "id", "type": "long"}]}"#;
+    const SCHEMA_V2: &str = r#"{"type": "record", "name": "sample", "fields": [{"name": "id", "type": "long"}, {"name": "name", "type": "string", "default": ""}]}"#;
+
+    struct MockSchemaRegistry {}
+
+    #[async_trait]
+    impl SchemaRegistryClient for MockSchemaRegistry {
+        async fn list_subjects(&self) -> Result<Vec<String>> {
+            todo!()
+        }
+        async fn get_subject(&self, _: &str) -> Result<Subject> {
+            todo!()
+        }
+        async fn get_schema_by_id(&self, id: i32) -> Result<ApacheAvroSchema> {
+            Ok(ApacheAvroSchema::parse_str(if id == 1 { SCHEMA_V1 } else { SCHEMA_V2 }).unwrap())
+        }
+        async fn get_raw_schema_by_id(&self, _: i32) -> Result<RawSchema> {
+            todo!()
+        }
+        async fn get_schema_version(&self, _: &str, _: Option<i32>) -> Result<Schema> {
+            todo!()
+        }
+        async fn delete_subject(&self, _: &str) -> Result<()> {
+            todo!()
+        }
+        async fn delete_version(&self, _: &str, _: i32) -> Result<()> {
+            todo!()
+        }
+    }
+
+    #[test]
+    fn test_export_template() {
+        assert_eq!(
+            get_export_template(Some("SELECT * FROM {:topic} LIMIT {:limit} OFFSET {:offset};\n")),
+            "SELECT * FROM {:topic} LIMIT {:limit} OFFSET {:offset}"
+        );
+    }
+
+    #[test]
+    fn test_csv_field() {
+        assert_eq!(to_csv_field("simple"), "simple");
+        assert_eq!(to_csv_field("a,b"), "\"a,b\"");
+        assert_eq!(to_csv_field(r#"{"a":"b"}"#), r#""{""a"":""b""}""#);
+        assert_eq!(to_csv_field("multi\nline"), "\"multi\nline\"");
+    }
+
+    #[tokio::test]
+    async fn test_export_jsonl() {
+        let records: Vec<_> = (0..3).map(get_test_record).collect();
+        let topic_store = get_topic_store(&records).await;
+        let dir = tempfile::tempdir().unwrap();
+        let output_path = dir.path().join("export.jsonl");
+        let progress = Arc::new(Mutex::new(vec![]));
+
+        let count = RecordExporter::<MockSchemaRegistry>::new(None)
+            .export(topic_store, None, &ExportFormat::Jsonl, &output_path, {
+                let progress = progress.clone();
+                move |count| progress.lock().unwrap().push(count)
+            })
+            .await
+            .unwrap();
+
+        // the default query returns the newest records first
+        let exported: Vec<ParsedKafkaRecord> = fs::read_to_string(&output_path)
+            .unwrap()
+            .lines()
+            .map(|line| serde_json::from_str(line).unwrap())
+            .collect();
+        let expected: Vec<_> = records
+            .into_iter()
+            .rev()
+            .map(|r| ParsedKafkaRecord { raw_payload: None, ..r })
+            .collect();
+        assert_eq!(count, 3);
+        assert_eq!(exported, expected);
+        assert_eq!(*progress.lock().unwrap(), vec![3]);
+    }
+
+    #[tokio::test]
+    async fn test_export_csv_ignores_the_query_limit() {
+        let records: Vec<_> = (0..3).map(get_test_record).collect();
+        let topic_store = get_topic_store(&records).await;
+        let dir = tempfile::tempdir().unwrap();
+        let output_path = dir.path().join("export.csv");
+        let query = "SELECT partition, offset, timestamp, key, payload FROM {:topic} ORDER BY offset LIMIT {:limit} OFFSET {:offset}";
+
+        let count = RecordExporter::<MockSchemaRegistry>::new(None)
+            .export(topic_store, Some(query), &ExportFormat::Csv, &output_path, |_| {})
+            .await
+            .unwrap();
+
+        assert_eq!(count, 3);
+        assert_eq!(
+            fs::read_to_string(&output_path).unwrap(),
+            "partition,offset,timestamp,key,payload,headers\n\
+            0,0,1000,key-0,\"{\"\"id\"\":0}\",[]\n\
+            0,1,1001,key-1,\"{\"\"id\"\":1}\",[]\n\
+            0,2,1002,key-2,\"{\"\"id\"\":2}\",[]\n"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_export_avro() {
+        // the last record is produced with a newer schema and resolved to the one of the first record
+        let mut records: Vec<_> = (0..3).map(get_test_record).collect();
+        records[2].payload_schema_id = Some(2);
+        records[2].raw_payload = Some(get_avro_payload(2, SCHEMA_V2, 2));
+        let topic_store = get_topic_store(&records).await;
+        let dir = tempfile::tempdir().unwrap();
+        let output_path = dir.path().join("export.avro");
+        // the query is wrapped to get the schemas, so it can end with a comment
+        let query = "SELECT * FROM {:topic} ORDER BY offset -- oldest first";
+
+        let count = RecordExporter::new(Some(Arc::new(MockSchemaRegistry {})))
+            .export(topic_store, Some(query), &ExportFormat::Avro, &output_path, |_| {})
+            .await
+            .unwrap();
+
+        let reader = Reader::new(File::open(&output_path).unwrap()).unwrap();
+        assert_eq!(reader.writer_schema(), &ApacheAvroSchema::parse_str(SCHEMA_V1).unwrap());
+        let values: Vec<_> = reader.map(|v| v.unwrap()).collect();
+        assert_eq!(count, 3);
+        assert_eq!(
+            values,
+            (0..3)
+                .map(|id| AvroValue::Record(vec![("id".into(), AvroValue::Long(id))]))
+                .collect::<Vec<_>>()
+        );
+    }
+
+    #[tokio::test]
+    async fn test_export_avro_without_schema_id() {
+        let topic_store = get_topic_store(&[get_test_record(0)]).await;
+        let dir = tempfile::tempdir().unwrap();
+        let query = "SELECT partition, offset, timestamp, key, payload FROM {:topic}";
+
+        let res = RecordExporter::new(Some(Arc::new(MockSchemaRegistry {})))
+            .export(
+                topic_store,
+                Some(query),
+                &ExportFormat::Avro,
+                &dir.path().join("export.avro"),
+                |_| {},
+            )
+            .await;
+
+        assert!(res.is_err());
+    }
+
+    async fn get_topic_store(records: &[ParsedKafkaRecord]) -> Arc<TopicStore> {
+        let parser: RecordParser = RecordParser::new(None);
+        let topic_store =
+            TopicStore::from_app_store(Arc::new(AppStore::new()), Arc::new(parser), "cluster_id", "topic_name").await;
+        topic_store.store_records(records).await.unwrap();
+        Arc::new(topic_store)
+    }
+
+    fn get_avro_payload(id: i32, schema: &str, value: i64) -> Vec<u8> {
+        let schema = ApacheAvroSchema::parse_str(schema).unwrap();
+        let mut fields = vec![("id".to_string(), AvroValue::Long(value))];
+        if id == 2 {
+            fields.push(("name".into(), AvroValue::String("new".into())));
+        }
+        let mut raw = vec![0x00];
+        raw.extend(id.to_be_bytes());
+        raw.extend(to_avro_datum(&schema, AvroValue::Record(fields)).unwrap());
+        raw
+    }
+
+    fn get_test_record(offset: i64) -> ParsedKafkaRecord {
+        ParsedKafkaRecord {
+            payload: Some(format!("{{\"id\":{}}}", offset)),
+            key: Some(format!("key-{}", offset)),
+            topic: "topic_name".into(),
+            timestamp: Some(1000 + offset),
+            partition: 0,
+            offset,
+            headers: vec![],
+            key_format: None,
+            payload_format: None,
+            validation_errors: None,
+            parse_error: None,
+            key_schema_id: None,
+            payload_schema_id: Some(1),
+            raw_key: None,
+            raw_payload: Some(get_avro_payload(1, SCHEMA_V1, offset)),
+            projections: HashMap::new(),
+        }
+    }
+}
@@ -1,6 +1,8 @@
 mod app_store;
 mod error;
+mod exporter;
 mod topic_store;
 
-pub use app_store::AppStore;
+pub use app_store::{AppStore, ConsumerSession};
+pub use exporter::{ExportFormat, RecordExporter};
 pub use topic_store::TopicStore;
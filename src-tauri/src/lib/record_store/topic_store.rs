@@ -1,9 +1,11 @@
 use crate::lib::{
-    parser::{Parser, ParserMode, RecordParser},
+    configuration::{StoreLimits, TopicSettings},
+    consumer::ConsumerOffsetConfiguration,
+    parser::Parser,
     types::{ParsedKafkaRecord, RawKafkaRecord},
     Result,
 };
-use std::sync::Arc;
+use std::{collections::HashMap, sync::Arc};
 
 use super::app_store::{AppStore, Query};
 
@@ -11,13 +13,13 @@ pub struct TopicStore {
     cluster_id: String,
     topic_name: String,
     app_store: Arc<AppStore>,
-    parser: Arc<RecordParser>,
+    parser: Arc<dyn Parser + Send + Sync>,
 }
 
 impl TopicStore {
     pub async fn from_app_store(
         app_store: Arc<AppStore>,
-        parser: Arc<RecordParser>,
+        parser: Arc<dyn Parser + Send + Sync>,
         cluster_id: &str,
         topic_name: &str,
     ) -> Self {
@@ -56,14 +58,92 @@ impl TopicStore {
         }
     }
 
-    pub async fn insert_record(&self, record: &RawKafkaRecord) -> Result<()> {
-        let parsed_record = if let Ok(avro_record) = self.parser.parse_record(record, ParserMode::Avro).await {
-            Ok(avro_record)
-        } else {
-            self.parser.parse_record(record, ParserMode::String).await
-        }?;
+    // blocking, the records of the query are passed to on_records in chunks, the store
+    // is not locked while on_records runs. The limit and the offset of the query are ignored
+    pub fn stream_records(
+        &self,
+        query: &str,
+        on_records: impl FnMut(Vec<ParsedKafkaRecord>) -> Result<()>,
+    ) -> Result<()> {
+        self.app_store.stream_records(
+            &Query {
+                cluster_id: self.cluster_id.clone(),
+                topic_name: self.topic_name.clone(),
+                offset: 0,
+                limit: -1,
+                query_template: query.into(),
+            },
+            on_records,
+        )
+    }
+
+    pub async fn get_payload_schema_ids(&self, query: &str) -> Result<Vec<i32>> {
+        self.app_store
+            .get_payload_schema_ids(&Query {
+                cluster_id: self.cluster_id.clone(),
+                topic_name: self.topic_name.clone(),
+                offset: 0,
+                limit: -1,
+                query_template: query.into(),
+            })
+            .await
+    }
+
+    // evict the oldest records exceeding the store limits
+    pub async fn evict_records(&self) -> Result<usize> {
+        self.app_store.evict_records(&self.cluster_id, &self.topic_name).await
+    }
+
+    // offset of the last record stored in each partition
+    pub async fn get_last_offsets(&self) -> Result<HashMap<i32, i64>> {
+        self.app_store
+            .get_last_offsets(&self.cluster_id, &self.topic_name)
+            .await
+    }
+
+    pub async fn get_evicted_count(&self) -> Result<usize> {
+        self.app_store
+            .get_evicted_count(&self.cluster_id, &self.topic_name)
+            .await
+    }
+
+    // limits of this topic and of all the topics
+    pub fn get_limits(&self) -> (StoreLimits, StoreLimits) {
+        (self.app_store.get_topic_limits(), self.app_store.get_global_limits())
+    }
+
+    pub async fn search_records(&self, search: &str, offset: i64, limit: i64) -> Result<Vec<ParsedKafkaRecord>> {
         self.app_store
-            .insert_record(&self.cluster_id, &self.topic_name, &parsed_record)
+            .search_records(&self.cluster_id, &self.topic_name, search, offset, limit)
+            .await
+    }
+
+    pub async fn validate_query(&self, query: &str) -> Result<()> {
+        self.app_store
+            .validate_query(&Query {
+                cluster_id: self.cluster_id.clone(),
+                topic_name: self.topic_name.clone(),
+                offset: 0,
+                limit: 0,
+                query_template: query.into(),
+            })
+            .await
+    }
+
+    pub async fn parse_record(&self, record: &RawKafkaRecord, settings: &TopicSettings) -> Result<ParsedKafkaRecord> {
+        self.parser.parse_record(record, settings).await
+    }
+
+    // store the parsed records in a single transaction
+    pub async fn store_records(&self, records: &[ParsedKafkaRecord]) -> Result<()> {
+        self.app_store
+            .insert_records(&self.cluster_id, &self.topic_name, records)
+            .await
+    }
+
+    pub async fn set_projections(&self, projections: &HashMap<String, String>) -> Result<()> {
+        self.app_store
+            .set_projections(&self.cluster_id, &self.topic_name, projections)
             .await
     }
 
@@ -71,6 +151,12 @@ impl TopicStore {
         self.app_store.clear(&self.cluster_id, &self.topic_name).await
     }
 
+    pub async fn save_session(&self, offset_config: &ConsumerOffsetConfiguration, started_at: i64) -> Result<()> {
+        self.app_store
+            .save_session(&self.cluster_id, &self.topic_name, offset_config, started_at)
+            .await
+    }
+
     pub async fn get_size(&self, query: Option<&str>) -> Result<usize> {
         if let Some(query) = query {
             self.app_store
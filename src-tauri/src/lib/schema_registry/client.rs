@@ -10,18 +10,15 @@ use apache_avro::Schema as AvroSchema;
 
 use super::error::{Result, SchemaRegistryError};
 use super::http_client::{HttpClient, ReqwestClient};
-use super::types::{BasicAuth, Schema, Subject};
-
-#[derive(Deserialize)]
-struct GetSchemaByIdResult {
-    pub schema: String,
-}
+use super::types::{BasicAuth, RawSchema, Schema, SchemaType, Subject};
 
 #[async_trait]
 pub trait SchemaRegistryClient {
     async fn list_subjects(&self) -> Result<Vec<String>>;
     async fn get_subject(&self, subject_name: &str) -> Result<Subject>;
     async fn get_schema_by_id(&self, id: i32) -> Result<AvroSchema>;
+    async fn get_raw_schema_by_id(&self, id: i32) -> Result<RawSchema>;
+    async fn get_schema_version(&self, subject_name: &str, version: Option<i32>) -> Result<Schema>;
     async fn delete_subject(&self, subject_name: &str) -> Result<()>;
     async fn delete_version(&self, subject_name: &str, version: i32) -> Result<()>;
 }
@@ -34,6 +31,7 @@ where
     http_client: C,
     endpoint: String,
     schema_cache_by_id: Arc<Mutex<HashMap<i32, AvroSchema>>>,
+    raw_schema_cache_by_id: Arc<Mutex<HashMap<i32, RawSchema>>>,
 }
 
 impl CachedSchemaRegistry<ReqwestClient> {
@@ -63,6 +61,7 @@ where
             http_client,
             endpoint: endpoint.into(),
             schema_cache_by_id: Arc::new(Mutex::new(HashMap::new())),
+            raw_schema_cache_by_id: Arc::new(Mutex::new(HashMap::new())),
         }
     }
 }
@@ -108,8 +107,12 @@ where
             Ok(cached.clone())
         } else {
             trace!("Schema not found in cache, retrieving");
-            let url = Url::parse(&self.endpoint)?.join(format!("/schemas/ids/{}", id).as_str())?;
-            let schema: GetSchemaByIdResult = self.http_client.get(url.as_str()).await?;
+            let schema = self.get_raw_schema_by_id(id).await?;
+            if schema.schema_type != SchemaType::Avro {
+                return Err(SchemaRegistryError::SchemaParsing {
+                    message: format!("The schema {} is not an avro schema ({:?})", id, schema.schema_type),
+                });
+            }
             let schema =
                 AvroSchema::parse_str(schema.schema.as_str()).map_err(|err| SchemaRegistryError::SchemaParsing {
                     message: format!("{}\n{}", "Unable to parse the schema from schema registry", err),
@@ -118,6 +121,27 @@ where
             Ok(schema)
         }
     }
+
+    async fn get_raw_schema_by_id(&self, id: i32) -> Result<RawSchema> {
+        let mut cache = self.raw_schema_cache_by_id.lock().await;
+        if let Some(cached) = cache.get(&id) {
+            Ok(cached.clone())
+        } else {
+            let url = Url::parse(&self.endpoint)?.join(format!("/schemas/ids/{}", id).as_str())?;
+            let schema: RawSchema = self.http_client.get(url.as_str()).await?;
+            cache.insert(id, schema.clone());
+            Ok(schema)
+        }
+    }
+
+    // retrieve a specific version of the subject or the latest if the version is not specified
+    async fn get_schema_version(&self, subject_name: &str, version: Option<i32>) -> Result<Schema> {
+        let version = version.map(|v| v.to_string()).unwrap_or_else(|| "latest".into());
+        debug!("Get subject {} version {}", subject_name, version);
+        let url =
+            Url::parse(&self.endpoint)?.join(format!("/subjects/{}/versions/{}", subject_name, version).as_str())?;
+        Ok(self.http_client.get(url.as_str()).await?)
+    }
 }
 
 impl<C> CachedSchemaRegistry<C>
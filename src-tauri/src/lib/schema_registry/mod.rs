@@ -6,4 +6,4 @@ mod types;
 pub use client::CachedSchemaRegistry;
 pub use client::SchemaRegistryClient;
 pub use error::{Result, SchemaRegistryError};
-pub use types::{BasicAuth, Schema, Subject};
+pub use types::{BasicAuth, RawSchema, Schema, SchemaReference, SchemaType, Subject};
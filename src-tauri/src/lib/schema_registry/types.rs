@@ -12,6 +12,37 @@ pub struct Schema {
     pub id: i32,
     pub version: i32,
     pub schema: String,
+    #[serde(rename = "schemaType", default)]
+    pub schema_type: SchemaType,
+    #[serde(default)]
+    pub references: Vec<SchemaReference>,
+}
+
+// schema definition returned by the registry when retrieving a schema by id
+#[derive(Debug, Deserialize, Serialize, Clone)]
+pub struct RawSchema {
+    pub schema: String,
+    #[serde(rename = "schemaType", default)]
+    pub schema_type: SchemaType,
+    #[serde(default)]
+    pub references: Vec<SchemaReference>,
+}
+
+// the registry omits the schemaType field for avro schemas
+#[derive(Debug, Deserialize, Serialize, Clone, Default, PartialEq, Eq)]
+#[serde(rename_all = "UPPERCASE")]
+pub enum SchemaType {
+    #[default]
+    Avro,
+    Protobuf,
+    Json,
+}
+
+#[derive(Debug, Deserialize, Serialize, Clone)]
+pub struct SchemaReference {
+    pub name: String, //import path used in the referencing schema
+    pub subject: String,
+    pub version: i32,
 }
 
 #[derive(Clone)]
@@ -1,4 +1,7 @@
+use std::collections::HashMap;
+
 use serde::{Deserialize, Serialize};
+use serde_json::Value as JsonValue;
 
 #[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq)]
 pub struct KafkaRecord<T> {
@@ -8,8 +11,63 @@ pub struct KafkaRecord<T> {
     pub timestamp: Option<i64>,
     pub partition: i32,
     pub offset: i64,
-    //todo: headers
+    pub headers: Vec<RecordHeader>,
+}
+
+// the headers keep the order and the duplicated keys of the kafka record
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq)]
+pub struct RecordHeader {
+    pub key: String,
+    pub value: Option<String>,
+    // the value is not valid utf-8, it's base64 encoded
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub binary: bool,
 }
 
 pub type RawKafkaRecord = KafkaRecord<Vec<u8>>;
-pub type ParsedKafkaRecord = KafkaRecord<String>;
+
+// format used to decode the key or the payload of a record
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq)]
+pub enum RecordFormat {
+    String,
+    Avro,
+    Protobuf,
+    JsonSchema,
+    Json,
+    Hex,
+    Base64,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq)]
+pub struct ParsedKafkaRecord {
+    pub payload: Option<String>,
+    pub key: Option<String>,
+    pub topic: String,
+    pub timestamp: Option<i64>,
+    pub partition: i32,
+    pub offset: i64,
+    pub headers: Vec<RecordHeader>,
+    #[serde(rename = "keyFormat")]
+    pub key_format: Option<RecordFormat>,
+    #[serde(rename = "payloadFormat")]
+    pub payload_format: Option<RecordFormat>,
+    // errors found validating the payload against its json schema, None if not validated
+    #[serde(rename = "validationErrors")]
+    pub validation_errors: Option<Vec<String>>,
+    // error returned decoding the key or the payload with the selected format
+    #[serde(rename = "parseError")]
+    pub parse_error: Option<String>,
+    // id of the registry schema used to decode the key and the payload
+    #[serde(rename = "keySchemaId")]
+    pub key_schema_id: Option<i32>,
+    #[serde(rename = "payloadSchemaId")]
+    pub payload_schema_id: Option<i32>,
+    // original bytes, stored to decode the record again without consuming it
+    #[serde(skip)]
+    pub raw_key: Option<Vec<u8>>,
+    #[serde(skip)]
+    pub raw_payload: Option<Vec<u8>>,
+    // values of the payload projections and of the other extra columns selected by the query
+    #[serde(default, skip_serializing_if = "HashMap::is_empty")]
+    pub projections: HashMap<String, JsonValue>,
+}
@@ -9,7 +9,11 @@ use crate::api::{
         get_last_offsets, get_topic_info, list_consumer_groups, list_topics, set_consumer_group,
     },
     configuration::{get_configuration, write_configuration},
-    consumer::{get_consumer_state, get_records_page, start_consumer, stop_consumer},
+    consumer::{
+        delete_session, export_records, get_consumer_state, get_records_page, list_sessions, search_records,
+        start_consumer, stop_consumer,
+    },
+    producer::{import_records, produce_record},
     schema_registry::{delete_subject, delete_subject_version, get_subject, list_subjects},
 };
 use api::AppState;
@@ -24,6 +28,13 @@ fn main() {
             stop_consumer,
             get_consumer_state,
             get_records_page,
+            search_records,
+            export_records,
+            list_sessions,
+            delete_session,
+            // producer
+            produce_record,
+            import_records,
             // schema
             list_subjects,
             get_subject,
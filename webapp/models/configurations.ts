@@ -1,8 +1,20 @@
+import { TopicSettings } from "./kafka";
+
 export type UserSettings = {
   clusters: Cluster[];
   theme: AppTheme;
   showNotifications?: boolean;
   useRegex?: boolean;
+  persistRecords?: boolean;
+  topicStoreLimits?: StoreLimits;
+  globalStoreLimits?: StoreLimits;
+};
+
+// the oldest records are evicted when a limit is exceeded
+export type StoreLimits = {
+  maxRecords?: number;
+  maxBytes?: number;
+  maxAge?: number; //time in ms, compared to the record timestamp
 };
 
 export type AppTheme = "Light" | "Dark";
@@ -13,6 +25,7 @@ export type Cluster = {
   endpoint: string;
   authentication: ClusterAuthentication;
   schemaRegistry: SchemaRegistry | null;
+  topicSettings?: Record<string, TopicSettings>;
 };
 
 export type ClusterAuthentication =
@@ -1,3 +1,5 @@
+import { StoreLimits } from "./configurations";
+
 export type TopicInfo = {
   name: string;
   partitions: PartitionInfo[];
@@ -5,17 +7,77 @@ export type TopicInfo = {
   configurations: any;
 };
 
+export type RecordFormat = "String" | "Avro" | "Protobuf" | "JsonSchema" | "Json" | "Hex" | "Base64";
+
+export type ParserMode =
+  | "Auto"
+  | "String"
+  | "Avro"
+  | "Protobuf"
+  | { JsonSchema: { validate: boolean } }
+  | "Json"
+  | "Hex"
+  | "Base64";
+
+export type AvroEncoding = "Friendly" | "Readable" | "AvroJson";
+
+// subject version used to resolve the avro records, latest if the version is not specified
+export type AvroReaderSchema = {
+  subject: string;
+  version?: number;
+};
+
+export type TopicSettings = {
+  keyMode: ParserMode;
+  payloadMode: ParserMode;
+  avroEncoding?: AvroEncoding;
+  keyReaderSchema?: AvroReaderSchema;
+  payloadReaderSchema?: AvroReaderSchema;
+  // json paths of the payload exposed as columns of the topic table, by column name
+  projections?: Record<string, string>;
+};
+
 export type KafkaRecord = {
   key: string;
   payload: string;
   partition: number;
   offset: number;
   timestamp?: number;
+  headers?: { key: string; value: string | null; binary?: boolean }[];
+  keyFormat?: RecordFormat;
+  payloadFormat?: RecordFormat;
+  validationErrors?: string[]; //json schema validation, undefined if not validated
+  parseError?: string;
+  keySchemaId?: number;
+  payloadSchemaId?: number;
+  projections?: Record<string, unknown>;
 };
 
 export type ConsumerState = {
   isRunning: boolean;
+  isFinished: boolean;
   recordCount: number;
+  evictedCount: number;
+  topicLimits: StoreLimits;
+  globalLimits: StoreLimits;
+};
+
+export type PartitionProgress = {
+  partition: number;
+  offset: number; //last consumed offset
+  lag: number; //-1 if unknown
+};
+
+export type ConsumerEvent =
+  | "Started"
+  | "Stopped"
+  | { Error: { message: string } }
+  | { Progress: { recordCount: number; partitions: PartitionProgress[] } };
+
+export type ConsumerNotification = {
+  clusterId: string;
+  topic: string;
+  event: ConsumerEvent;
 };
 
 export type ConsumerSettingsFrom =
@@ -26,7 +88,30 @@ export type ConsumerSettingsFrom =
         start_timestamp: number; //time in ms
         stop_timestamp?: number; //time in ms
       };
-    };
+    }
+  | { PartitionOffsets: { offsets: Record<number, number> } } //partition -> offset
+  | { LastRecords: { count: number } };
+
+export type ConsumerSettingsTo = "Never" | { RecordCount: number } | "CurrentEnd";
+
+export type ExportFormat = "Jsonl" | "Csv" | "Avro";
+
+export type ExportNotification = {
+  clusterId: string;
+  topic: string;
+  outputPath: string;
+  recordCount: number;
+};
+
+export type ConsumerSession = {
+  clusterId: string;
+  topicName: string;
+  offsetConfig: ConsumerSettingsFrom;
+  startedAt: number; //time in ms
+  recordCount: number;
+  minTimestamp?: number;
+  maxTimestamp?: number;
+};
 
 export type ConsumerGroupInfo = {
   name: string;
@@ -50,3 +135,14 @@ export type PartitionOffset = {
   partitionId: number;
   offset: number;
 };
+
+type SchemaSelector = { Subject: string } | { Id: number };
+// AvroJson expects the payloads in the standard avro json encoding
+export type ProducerMode = "String" | { Avro: SchemaSelector } | { AvroJson: SchemaSelector };
+
+export type ImportFormat = "Jsonl" | "Csv";
+
+export type ImportResult = {
+  recordCount: number;
+  failures: { line: number; message: string }[];
+};
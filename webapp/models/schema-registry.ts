@@ -8,4 +8,6 @@ export type SchemaVersion = {
   id: number;
   version: number;
   schema: string;
+  schemaType: "AVRO" | "PROTOBUF" | "JSON";
+  references: { name: string; subject: string; version: number }[];
 };
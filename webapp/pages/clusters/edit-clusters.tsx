@@ -37,7 +37,8 @@ export const EditCluster = () => {
 
   const onSubmit = async (c: ClusterFormType) => {
     const newCluster = mapFormToCluster(c);
-    await editCluster(cluster.id, newCluster).then((_) => navigate("/clusters"));
+    // keep the settings of the topics
+    await editCluster(cluster.id, { ...newCluster, topicSettings: cluster.topicSettings }).then((_) => navigate("/clusters"));
   };
 
   return (
@@ -0,0 +1,81 @@
+import { ActionIcon, Group, Paper, Stack, Text, Title, Tooltip } from "@mantine/core";
+import { openConfirmModal } from "@mantine/modals";
+import { IconExternalLink, IconRefresh, IconTrash } from "@tabler/icons";
+import { useQuery } from "@tanstack/react-query";
+import dayjs from "dayjs";
+import { useNavigate } from "react-router-dom";
+import { ConsumerSession, ConsumerSettingsFrom } from "../../models";
+import { useUserSettings } from "../../providers";
+import { deleteSession, listSessions } from "../../tauri/consumer";
+
+export const Sessions = () => {
+  const { userSettings } = useUserSettings();
+  const navigate = useNavigate();
+  const { data, refetch } = useQuery(["listSessions"], listSessions);
+
+  const onDelete = (session: ConsumerSession) =>
+    openConfirmModal({
+      title: "Delete session",
+      children: (
+        <Text size="sm">
+          Are you sure to delete the {session.recordCount} records stored consuming {session.topicName}?
+        </Text>
+      ),
+      labels: { confirm: "Confirm", cancel: "Cancel" },
+      onConfirm: () => deleteSession(session.clusterId, session.topicName).then(() => refetch()),
+    });
+
+  const clusterName = (clusterId: string) => userSettings.clusters.find((c) => c.id == clusterId)?.name ?? clusterId;
+
+  return (
+    <Stack spacing={5}>
+      <Group position="apart">
+        <Title order={4}>Consumer sessions</Title>
+        <ActionIcon onClick={() => refetch()}>
+          <IconRefresh size={18} />
+        </ActionIcon>
+      </Group>
+      {data?.length == 0 && <Text size="sm">No stored sessions</Text>}
+      {data?.map((s) => (
+        <Paper key={`${s.clusterId}-${s.topicName}`} withBorder p={5}>
+          <Group position="apart" noWrap>
+            <Stack spacing={0}>
+              <Text size="sm" weight="bold">
+                {clusterName(s.clusterId)} / {s.topicName}
+              </Text>
+              <Text size="xs">
+                From {formatOffsetConfig(s.offsetConfig)}, started at {dayjs(s.startedAt).toISOString()}
+              </Text>
+              <Text size="xs">
+                {s.recordCount} records
+                {s.minTimestamp != undefined && s.maxTimestamp != undefined
+                  ? ` from ${dayjs(s.minTimestamp).toISOString()} to ${dayjs(s.maxTimestamp).toISOString()}`
+                  : ""}
+              </Text>
+            </Stack>
+            <Group spacing={0} noWrap>
+              <Tooltip label="Open">
+                <ActionIcon onClick={() => navigate(`/cluster/${s.clusterId}/topic/${s.topicName}`)}>
+                  <IconExternalLink size={18} />
+                </ActionIcon>
+              </Tooltip>
+              <Tooltip label="Delete">
+                <ActionIcon color="red" onClick={() => onDelete(s)}>
+                  <IconTrash size={18} />
+                </ActionIcon>
+              </Tooltip>
+            </Group>
+          </Group>
+        </Paper>
+      ))}
+    </Stack>
+  );
+};
+
+const formatOffsetConfig = (offsetConfig: ConsumerSettingsFrom): string => {
+  if (offsetConfig == "Beginning") return "the beginning";
+  if (offsetConfig == "End") return "the end";
+  if ("Custom" in offsetConfig) return dayjs(offsetConfig.Custom.start_timestamp).toISOString();
+  if ("PartitionOffsets" in offsetConfig) return "custom partition offsets";
+  return `the last ${offsetConfig.LastRecords.count} records`;
+};
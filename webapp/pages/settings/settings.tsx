@@ -5,6 +5,8 @@ import { PageHeader } from "../../components";
 import { AppTheme } from "../../models";
 import { useNotifications } from "../../providers";
 import { useUserSettings } from "../../providers/user-settings-provider";
+import { Sessions } from "./sessions";
+import { StoreLimitsSettings } from "./store-limits";
 
 export const Settings = () => {
   const { userSettings, setUserSettings } = useUserSettings();
@@ -52,9 +54,16 @@ export const Settings = () => {
             checked={userSettings.useRegex}
             onChange={(c) => setUserSettings((s) => ({ ...s, useRegex: c.target.checked }))}
           />
+          <Checkbox
+            label="Store the consumed records on disk (requires a restart)"
+            checked={userSettings.persistRecords ?? false}
+            onChange={(c) => setUserSettings((s) => ({ ...s, persistRecords: c.target.checked }))}
+          />
+          <StoreLimitsSettings />
           <Button onClick={clearFavorites}>
             <IconTrash size={18} /> Clear cache
           </Button>
+          <Sessions />
         </Stack>
       </Center>
     </Container>
@@ -0,0 +1,54 @@
+import { Group, NumberInput, Stack, Text, Title } from "@mantine/core";
+import { StoreLimits } from "../../models";
+import { useUserSettings } from "../../providers";
+
+type LimitsKey = "topicStoreLimits" | "globalStoreLimits";
+
+export const StoreLimitsSettings = () => {
+  const { userSettings, setUserSettings } = useUserSettings();
+
+  const setLimit = (key: LimitsKey, limit: keyof StoreLimits, value?: number) =>
+    setUserSettings((s) => ({ ...s, [key]: { ...s[key], [limit]: value } }));
+
+  // the limits are stored in bytes and ms, and edited in MB and hours
+  const LimitsInputs = ({ limitsKey, label }: { limitsKey: LimitsKey; label: string }) => {
+    const limits = userSettings[limitsKey] ?? {};
+    return (
+      <Group grow>
+        <NumberInput
+          label={`Max records ${label}`}
+          placeholder="No limit"
+          min={1}
+          value={limits.maxRecords}
+          onChange={(v) => setLimit(limitsKey, "maxRecords", v)}
+        />
+        <NumberInput
+          label="Max size (MB)"
+          placeholder="No limit"
+          min={1}
+          value={limits.maxBytes !== undefined ? limits.maxBytes / MB : undefined}
+          onChange={(v) => setLimit(limitsKey, "maxBytes", v !== undefined ? v * MB : undefined)}
+        />
+        <NumberInput
+          label="Max age (hours)"
+          placeholder="No limit"
+          min={1}
+          value={limits.maxAge !== undefined ? limits.maxAge / HOUR : undefined}
+          onChange={(v) => setLimit(limitsKey, "maxAge", v !== undefined ? v * HOUR : undefined)}
+        />
+      </Group>
+    );
+  };
+
+  return (
+    <Stack spacing={5}>
+      <Title order={4}>Record store limits</Title>
+      <Text size="xs">The oldest records are evicted when a limit is exceeded (requires a restart)</Text>
+      {LimitsInputs({ limitsKey: "topicStoreLimits", label: "per topic" })}
+      {LimitsInputs({ limitsKey: "globalStoreLimits", label: "in total" })}
+    </Stack>
+  );
+};
+
+const MB = 1024 * 1024;
+const HOUR = 60 * 60 * 1000;
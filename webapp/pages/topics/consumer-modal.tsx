@@ -1,11 +1,25 @@
-import { Chip, Stack, Title, Text, Group, Checkbox, Button, Divider } from "@mantine/core";
+import {
+  Chip,
+  Stack,
+  Title,
+  Text,
+  Group,
+  Checkbox,
+  Button,
+  Divider,
+  Select,
+  TextInput,
+  NumberInput,
+  Textarea,
+} from "@mantine/core";
 import { openModal, useModals } from "@mantine/modals";
 import { DateRangePicker, DatePicker, TimeRangeInput, TimeInput } from "@mantine/dates";
-import { ConsumerSettingsFrom } from "../../models/kafka";
+import { AvroEncoding, AvroReaderSchema, ConsumerSettingsFrom, ParserMode, TopicSettings } from "../../models/kafka";
 import { useForm } from "@mantine/form";
 import dayjs from "dayjs";
 import utc from "dayjs/plugin/utc";
 import { startConsumer } from "../../tauri/consumer";
+import { useUserSettings } from "../../providers";
 
 dayjs.extend(utc);
 
@@ -24,6 +38,8 @@ export const openConsumerModal = (props: ConsumerModalProps) => {
 
 const ModalBody = ({ clusterId, topicName }: ConsumerModalProps) => {
   const { closeAll } = useModals();
+  const { userSettings, setUserSettings } = useUserSettings();
+  const topicSettings = userSettings.clusters.find((c) => c.id == clusterId)?.topicSettings?.[topicName];
   const nowUTC = dayjs.utc().toDate();
   const zeroUTC = dayjs().set("h", 0).set("m", 0).set("s", 0).toDate();
   const form = useForm<ConsumerForm>({
@@ -34,6 +50,17 @@ const ModalBody = ({ clusterId, topicName }: ConsumerModalProps) => {
       timeInterval: [zeroUTC, zeroUTC],
       dateFrom: nowUTC,
       timeFrom: zeroUTC,
+      keyMode: parserModeToForm(topicSettings?.keyMode),
+      payloadMode: parserModeToForm(topicSettings?.payloadMode),
+      avroEncoding: topicSettings?.avroEncoding ?? "Friendly",
+      keyReaderSubject: topicSettings?.keyReaderSchema?.subject ?? "",
+      keyReaderVersion: topicSettings?.keyReaderSchema?.version,
+      payloadReaderSubject: topicSettings?.payloadReaderSchema?.subject ?? "",
+      payloadReaderVersion: topicSettings?.payloadReaderSchema?.version,
+      projections: Object.entries(topicSettings?.projections ?? {})
+        .map(([name, path]) => `${name} = ${path}`)
+        .join("\n"),
+      resume: false,
     },
     validate: {}, //todo
   });
@@ -62,7 +89,22 @@ const ModalBody = ({ clusterId, topicName }: ConsumerModalProps) => {
     }
   };
   const onSubmit = async (f: ConsumerForm) => {
-    await startConsumer(clusterId, topicName, getConsumerSettingFrom(f));
+    const settings: TopicSettings = {
+      keyMode: formToParserMode(f.keyMode),
+      payloadMode: formToParserMode(f.payloadMode),
+      avroEncoding: f.avroEncoding,
+      keyReaderSchema: toReaderSchema(f.keyReaderSubject, f.keyReaderVersion),
+      payloadReaderSchema: toReaderSchema(f.payloadReaderSubject, f.payloadReaderVersion),
+      projections: toProjections(f.projections),
+    };
+    // remember the formats for the next consumer of this topic
+    await setUserSettings((s) => ({
+      ...s,
+      clusters: s.clusters.map((c) =>
+        c.id != clusterId ? c : { ...c, topicSettings: { ...c.topicSettings, [topicName]: settings } }
+      ),
+    }));
+    await startConsumer(clusterId, topicName, getConsumerSettingFrom(f), undefined, settings, f.resume);
     closeAll();
   };
 
@@ -78,6 +120,43 @@ const ModalBody = ({ clusterId, topicName }: ConsumerModalProps) => {
           </Text>
         </Stack>
         <Divider />
+        <Group grow>
+          <Select label="Key format" data={parserModes} {...form.getInputProps("keyMode")} />
+          <Select label="Payload format" data={parserModes} {...form.getInputProps("payloadMode")} />
+        </Group>
+        <Select
+          label="Avro encoding"
+          data={[
+            { value: "Friendly", label: "Friendly" },
+            { value: "Readable", label: "Readable (ISO-8601 dates and timestamps)" },
+            { value: "AvroJson", label: "Avro JSON (can be produced back to Kafka)" },
+          ]}
+          {...form.getInputProps("avroEncoding")}
+        />
+        <Group grow>
+          <TextInput
+            label="Key reader schema subject"
+            placeholder={`${topicName}-key`}
+            {...form.getInputProps("keyReaderSubject")}
+          />
+          <NumberInput label="Version" placeholder="Latest" min={1} {...form.getInputProps("keyReaderVersion")} />
+        </Group>
+        <Group grow>
+          <TextInput
+            label="Payload reader schema subject"
+            placeholder={`${topicName}-value`}
+            {...form.getInputProps("payloadReaderSubject")}
+          />
+          <NumberInput label="Version" placeholder="Latest" min={1} {...form.getInputProps("payloadReaderVersion")} />
+        </Group>
+        <Textarea
+          label="Payload projections"
+          description="One column per line, queryable in the SQL filter"
+          placeholder="user_id = $.user.id"
+          autosize
+          minRows={2}
+          {...form.getInputProps("projections")}
+        />
         <Title size={15}>Start consuming from</Title>
         <Chip.Group position="left" multiple={false} {...form.getInputProps("from")}>
           <Chip value="End">End</Chip>
@@ -99,6 +178,10 @@ const ModalBody = ({ clusterId, topicName }: ConsumerModalProps) => {
             <TimeInput withSeconds label="From time (UTC)" {...form.getInputProps("timeFrom")} />
           </Stack>
         </Stack>
+        <Checkbox
+          label="Keep the stored records and resume after the last one of each partition"
+          {...form.getInputProps("resume", { type: "checkbox" })}
+        />
         <Group mt={10} position="right">
           <Button type="submit">Start 🚀</Button>
         </Group>
@@ -115,6 +198,37 @@ export const dateTimeToUnixTimeMs = (dateUTC: Date, timeUTC: Date): number => {
   return dayjs(new Date(dateTime)).unix() * 1000;
 };
 
+const parserModes = [
+  { value: "Auto", label: "Auto" },
+  { value: "String", label: "String" },
+  { value: "Avro", label: "Avro" },
+  { value: "Protobuf", label: "Protobuf" },
+  { value: "JsonSchema", label: "JSON Schema" },
+  { value: "Json", label: "JSON" },
+  { value: "Hex", label: "Hex" },
+  { value: "Base64", label: "Base64" },
+];
+
+// decode the avro records with the writer schema if the subject is not specified
+const toReaderSchema = (subject: string, version?: number): AvroReaderSchema | undefined =>
+  subject.trim() == "" ? undefined : { subject: subject.trim(), version };
+
+// one "name = json path" projection per line
+const toProjections = (value: string): Record<string, string> =>
+  Object.fromEntries(
+    value
+      .split("\n")
+      .map((line) => line.split("="))
+      .filter((parts) => parts.length >= 2 && parts[0].trim() != "")
+      .map(([name, ...path]) => [name.trim(), path.join("=").trim()])
+  );
+
+const parserModeToForm = (mode?: ParserMode): string =>
+  !mode ? "Auto" : typeof mode === "string" ? mode : "JsonSchema";
+
+const formToParserMode = (mode: string): ParserMode =>
+  mode == "JsonSchema" ? { JsonSchema: { validate: true } } : (mode as ParserMode);
+
 type ConsumerForm = {
   from: "Beginning" | "End" | "Custom";
   dateInterval: [Date, Date];
@@ -122,4 +236,13 @@ type ConsumerForm = {
   onlyBeginning: boolean;
   dateFrom: Date;
   timeFrom: Date;
+  keyMode: string;
+  payloadMode: string;
+  avroEncoding: AvroEncoding;
+  keyReaderSubject: string;
+  keyReaderVersion?: number;
+  payloadReaderSubject: string;
+  payloadReaderVersion?: number;
+  projections: string;
+  resume: boolean;
 };
@@ -0,0 +1,111 @@
+import { Button, Checkbox, Group, ScrollArea, Select, Stack, Text, TextInput, Title } from "@mantine/core";
+import { useForm } from "@mantine/form";
+import { openModal } from "@mantine/modals";
+import { open } from "@tauri-apps/api/dialog";
+import { useState } from "react";
+import { ImportFormat, ImportResult, ProducerMode } from "../../models/kafka";
+import { useNotifications } from "../../providers";
+import { importRecords } from "../../tauri/producer";
+
+type ImportModalProps = {
+  clusterId: string;
+  topicName: string;
+};
+
+export const openImportModal = (props: ImportModalProps) => {
+  openModal({
+    title: <Title order={3}>Import records</Title>,
+    children: <ModalBody {...props} />,
+    closeOnClickOutside: false,
+  });
+};
+
+type ImportForm = {
+  filePath: string;
+  format: ImportFormat;
+  avroSubject: string;
+  avroJson: boolean;
+};
+
+const ModalBody = ({ clusterId, topicName }: ImportModalProps) => {
+  const { success } = useNotifications();
+  const [isImporting, setIsImporting] = useState(false);
+  const [result, setResult] = useState<ImportResult | undefined>(undefined);
+  const form = useForm<ImportForm>({
+    initialValues: { filePath: "", format: "Jsonl", avroSubject: "", avroJson: false },
+    validate: { filePath: (v) => (v.length == 0 ? "Select the file to import" : null) },
+  });
+
+  const selectFile = async () => {
+    const filePath = await open({ filters: [{ name: "Records", extensions: ["jsonl", "json", "csv"] }] });
+    if (typeof filePath === "string") {
+      form.setFieldValue("filePath", filePath);
+      form.setFieldValue("format", filePath.toLowerCase().endsWith(".csv") ? "Csv" : "Jsonl");
+    }
+  };
+
+  const onSubmit = async ({ filePath, format, avroSubject, avroJson }: ImportForm) => {
+    setIsImporting(true);
+    const schema = { Subject: avroSubject };
+    const mode: ProducerMode =
+      avroSubject.length == 0 ? "String" : avroJson ? { AvroJson: schema } : { Avro: schema };
+    await importRecords(clusterId, topicName, filePath, format, mode)
+      .then((res) => {
+        setResult(res);
+        success(`Import to ${topicName}`, `${res.recordCount} records produced`);
+      })
+      .finally(() => setIsImporting(false));
+  };
+
+  return (
+    <form onSubmit={form.onSubmit(onSubmit)}>
+      <Stack>
+        <Group align="end" noWrap>
+          <TextInput label="File" sx={{ flexGrow: 1 }} readOnly {...form.getInputProps("filePath")} />
+          <Button variant="default" onClick={selectFile}>
+            Browse
+          </Button>
+        </Group>
+        <Select
+          label="Format"
+          data={[
+            { value: "Jsonl", label: "JSON Lines" },
+            { value: "Csv", label: "CSV" },
+          ]}
+          {...form.getInputProps("format")}
+        />
+        <TextInput
+          label="Avro subject"
+          placeholder="Leave empty to produce the payloads as strings"
+          {...form.getInputProps("avroSubject")}
+        />
+        <Checkbox
+          label="Payloads in the Avro JSON encoding"
+          disabled={form.values.avroSubject.length == 0}
+          {...form.getInputProps("avroJson", { type: "checkbox" })}
+        />
+        {result && (
+          <>
+            <Text size="sm">
+              {result.recordCount} records produced, {result.failures.length} failures
+            </Text>
+            {result.failures.length > 0 && (
+              <ScrollArea style={{ height: 150 }}>
+                {result.failures.map((f) => (
+                  <Text key={f.line} size="xs" color="red">
+                    Line {f.line}: {f.message}
+                  </Text>
+                ))}
+              </ScrollArea>
+            )}
+          </>
+        )}
+        <Group mt={10} position="right">
+          <Button type="submit" loading={isImporting}>
+            Import 🚀
+          </Button>
+        </Group>
+      </Stack>
+    </form>
+  );
+};
@@ -6,27 +6,32 @@ import { useVirtualizer } from "@tanstack/react-virtual";
 import dayjs from "dayjs";
 import React, { useEffect, useState } from "react";
 import { KafkaRecord } from "../../models/kafka";
-import { getRecordsPage } from "../../tauri/consumer";
+import { getRecordsPage, searchRecords } from "../../tauri/consumer";
 
 type RecordsListProps = {
   clusterId: string;
   topic: string;
   query: string;
+  // full text search, replaces the query when not empty
+  search?: string;
   heightOffset?: number;
 };
 
 const RECORD_PAGE_HEIGHT = 135;
 
 export const RecordsList = (props: RecordsListProps) => {
-  const { clusterId, topic, heightOffset, query } = props;
+  const { clusterId, topic, heightOffset, query, search } = props;
 
   const [state, setState] = useState<{ windowHeight: number }>({
     windowHeight: window.innerHeight,
   });
 
   const { data, hasNextPage, fetchNextPage, isFetchingNextPage, isLoading } = useInfiniteQuery(
-    ["fetchRecords", clusterId, topic, query],
-    async ({ pageParam = 0 }) => await getRecordsPage(clusterId, topic, pageParam ?? 0, query),
+    ["fetchRecords", clusterId, topic, query, search],
+    async ({ pageParam = 0 }) =>
+      search
+        ? await searchRecords(clusterId, topic, pageParam ?? 0, search)
+        : await getRecordsPage(clusterId, topic, pageParam ?? 0, query),
     {
       getNextPageParam: (lastPage, _) => lastPage.nextPage,
       getPreviousPageParam: (firstPage, _) => firstPage.prevPage,
@@ -78,7 +83,9 @@ export const RecordsList = (props: RecordsListProps) => {
               width: "100%",
             }}>
             {isLoading && <Loader></Loader>}
-            {!isLoading && allRecords.length == 0 && <Text>No records querying the consumed records</Text>}
+            {!isLoading && allRecords.length == 0 && (
+              <Text>{search ? "No records matching the search" : "No records querying the consumed records"}</Text>
+            )}
           </Center>
           {rowVirtualizer.getVirtualItems().map((virtualItem) => {
             if (allRecords.length > 0 && hasNextPage && allRecords.length <= virtualItem.index) {
@@ -139,6 +146,7 @@ const KafkaRecordCard = ({
   style: React.CSSProperties;
 }) => {
   const timestamp = record?.timestamp ? dayjs(record.timestamp).toISOString() : "N/A";
+  const headers = record?.headers && record.headers.length > 0 ? JSON.stringify(record.headers) : "N/A";
   return (
     <Paper
       shadow="xs"
@@ -152,6 +160,24 @@ const KafkaRecordCard = ({
         <LabelValue label="partition: " value={record?.partition} />
         <LabelValue label="offset: " value={record?.offset} />
         <LabelValue label="timestamp: " value={timestamp} />
+        <LabelValue label="headers: " value={headers} />
+        <LabelValue label="format: " value={`${record?.keyFormat ?? "N/A"}/${record?.payloadFormat ?? "N/A"}`} />
+        {record?.validationErrors && (
+          <LabelValue
+            label="schema: "
+            value={record.validationErrors.length == 0 ? "valid" : record.validationErrors.join(", ")}
+          />
+        )}
+        {(record?.keySchemaId != undefined || record?.payloadSchemaId != undefined) && (
+          <LabelValue
+            label="schema id: "
+            value={`${record.keySchemaId ?? "N/A"}/${record.payloadSchemaId ?? "N/A"}`}
+          />
+        )}
+        {record?.parseError && <LabelValue label="parse error: " value={record.parseError} />}
+        {Object.entries(record?.projections ?? {}).map(([name, value]) => (
+          <LabelValue key={name} label={`${name}: `} value={JSON.stringify(value)} />
+        ))}
       </Group>
       <CustomPrism mt={2} copyLabel="Copy" language={"json"}>
         {record?.payload ?? ""}
@@ -1,25 +1,41 @@
-import { ActionIcon, Badge, Button, Center, Container, Group, Loader, Text, Menu } from "@mantine/core";
-import { IconInfoCircle, IconTool, IconTrash } from "@tabler/icons";
+import { ActionIcon, Badge, Button, Center, Container, Group, Loader, Text, Menu, TextInput } from "@mantine/core";
+import { IconDownload, IconInfoCircle, IconSearch, IconTool, IconTrash, IconUpload } from "@tabler/icons";
 import { RecordsList } from "./record-list";
-import { getConsumerState, stopConsumer } from "../../tauri/consumer";
+import {
+  exportRecords,
+  getConsumerState,
+  listenConsumerEvents,
+  listenExportEvents,
+  stopConsumer,
+} from "../../tauri/consumer";
 import { PageHeader } from "../../components";
 import { openConsumerModal } from "./consumer-modal";
+import { openImportModal } from "./import-modal";
 import { useQuery } from "@tanstack/react-query";
 import { deleteTopic, getLastOffsets, getTopicInfo } from "../../tauri/admin";
-import { useState } from "react";
+import { useEffect, useState } from "react";
 import CodeEditor from "@uiw/react-textarea-code-editor";
 import { Allotment } from "allotment";
 import { useNavigate } from "react-router-dom";
 import { openConfirmModal } from "@mantine/modals";
 import { useNotifications } from "../../providers";
+import { save } from "@tauri-apps/api/dialog";
+import { hideNotification, showNotification, updateNotification } from "@mantine/notifications";
+import { ExportFormat } from "../../models";
 
 export const Topic = ({ clusterId, topicName }: { clusterId: string; topicName: string }) => {
-  const { data, isLoading } = useQuery(
-    ["getConsumerState", clusterId, topicName],
-    () => getConsumerState(clusterId, topicName),
-    { refetchInterval: 500 }
+  const { data, isLoading, refetch } = useQuery(["getConsumerState", clusterId, topicName], () =>
+    getConsumerState(clusterId, topicName)
   );
 
+  // refresh the consumer state when the backend notifies a change
+  useEffect(() => {
+    const unlisten = listenConsumerEvents(clusterId, topicName, () => refetch());
+    return () => {
+      unlisten.then((f) => f());
+    };
+  }, [clusterId, topicName]);
+
   const { data: estimatedRecord } = useQuery(["getLastOffsets", clusterId, topicName], () =>
     getLastOffsets(clusterId, [topicName])
       .then((res) => res[topicName].map((po) => po.offset))
@@ -38,10 +54,10 @@ export const Topic = ({ clusterId, topicName }: { clusterId: string; topicName:
     data.isRunning ? await stopConsumer(clusterId, topicName) : openConsumerModal({ clusterId, topicName });
   };
 
-  const defaultQuery =
-    "SELECT partition, offset, timestamp, key, payload FROM {:topic}\nORDER BY timestamp desc LIMIT {:limit} OFFSET {:offset}";
+  const defaultQuery = "SELECT * FROM {:topic}\nORDER BY timestamp desc LIMIT {:limit} OFFSET {:offset}";
   const [query, setQuery] = useState<string>(defaultQuery);
   const [modalState, setModalState] = useState<{ opened: boolean; query: string }>({ opened: false, query });
+  const [search, setSearch] = useState<{ text: string; applied: string }>({ text: "", applied: "" });
 
   return (
     <>
@@ -53,7 +69,7 @@ export const Topic = ({ clusterId, topicName }: { clusterId: string; topicName:
               subtitle={`Estimated Records: ${estimatedRecord ?? "..."}, Cleanup policy: ${
                 topicInfo?.cleanupPolicy ?? "..."
               }, Partitions: ${topicInfo?.partitionCount ?? "..."}`}>
-              <Tools clusterId={clusterId} topic={topicName} />
+              <Tools clusterId={clusterId} topic={topicName} query={query == defaultQuery ? undefined : query} />
             </PageHeader>
             {isLoading && (
               <Center mt={10}>
@@ -80,8 +96,28 @@ export const Topic = ({ clusterId, topicName }: { clusterId: string; topicName:
                     onClick={() => setModalState({ ...modalState, opened: !modalState.opened })}>
                     {modalState.opened ? "Hide query" : "Edit query"}
                   </Button>
+                  <TextInput
+                    mb={10}
+                    size="xs"
+                    placeholder="Search key, payload and headers"
+                    icon={<IconSearch size={14} />}
+                    value={search.text}
+                    onChange={(e) => setSearch({ ...search, text: e.currentTarget.value })}
+                    onKeyDown={(e) => e.key == "Enter" && setSearch({ ...search, applied: search.text.trim() })}
+                  />
+                  {data.evictedCount > 0 && (
+                    <Text mb={10} size="xs" color="dimmed">
+                      {data.evictedCount} oldest records evicted by the store limits
+                    </Text>
+                  )}
                 </Group>
-                <RecordsList clusterId={clusterId} topic={topicName} heightOffset={140} query={query} />
+                <RecordsList
+                  clusterId={clusterId}
+                  topic={topicName}
+                  heightOffset={140}
+                  query={query}
+                  search={search.applied}
+                />
               </>
             )}
           </Container>
@@ -129,7 +165,9 @@ export const Topic = ({ clusterId, topicName }: { clusterId: string; topicName:
   );
 };
 
-const Tools = ({ clusterId, topic }: { clusterId: string; topic: string }) => {
+const exportExtensions: Record<ExportFormat, string> = { Jsonl: "jsonl", Csv: "csv", Avro: "avro" };
+
+const Tools = ({ clusterId, topic, query }: { clusterId: string; topic: string; query?: string }) => {
   const navigate = useNavigate();
   const { success } = useNotifications();
   const openDeleteTopicModal = () =>
@@ -153,6 +191,24 @@ const Tools = ({ clusterId, topic }: { clusterId: string; topic: string }) => {
 
   const openInfoModal = () => console.log("Not implemented yet");
 
+  // export the records matching the current query, the progress is shown in a notification
+  const exportTo = async (exportFormat: ExportFormat) => {
+    const outputPath = await save({ defaultPath: `${topic}.${exportExtensions[exportFormat]}` });
+    if (!outputPath) return;
+    const id = `export-${clusterId}-${topic}`;
+    const title = `Export ${topic}`;
+    showNotification({ id, title, message: "Starting the export", loading: true, autoClose: false });
+    const unlisten = await listenExportEvents(clusterId, topic, ({ recordCount }) =>
+      updateNotification({ id, title, message: `${recordCount} records exported`, loading: true, autoClose: false })
+    );
+    await exportRecords(clusterId, topic, exportFormat, outputPath, query)
+      .then((count) => success(title, `${count} records exported to ${outputPath}`, true))
+      .finally(() => {
+        unlisten();
+        hideNotification(id);
+      });
+  };
+
   return (
     <Menu position="bottom-end" trigger="hover" openDelay={100} closeDelay={400}>
       <Menu.Target>
@@ -165,6 +221,20 @@ const Tools = ({ clusterId, topic }: { clusterId: string; topic: string }) => {
         <Menu.Item icon={<IconInfoCircle size={14} />} onClick={openInfoModal}>
           Topic info
         </Menu.Item>
+        <Menu.Label>Import/Export records</Menu.Label>
+        <Menu.Item icon={<IconDownload size={14} />} onClick={() => exportTo("Jsonl")}>
+          Export to JSON Lines
+        </Menu.Item>
+        <Menu.Item icon={<IconDownload size={14} />} onClick={() => exportTo("Csv")}>
+          Export to CSV
+        </Menu.Item>
+        <Menu.Item icon={<IconDownload size={14} />} onClick={() => exportTo("Avro")}>
+          Export to Avro
+        </Menu.Item>
+        <Menu.Item icon={<IconUpload size={14} />} onClick={() => openImportModal({ clusterId, topicName: topic })}>
+          Import from file
+        </Menu.Item>
+        <Menu.Divider />
         <Menu.Item color="red" icon={<IconTrash size={14} />} onClick={openDeleteTopicModal}>
           Delete topic
         </Menu.Item>
@@ -1,5 +1,17 @@
 import { invoke } from "@tauri-apps/api";
-import { ConsumerSettingsFrom, ConsumerState, KafkaRecord } from "../models/kafka";
+import { listen, UnlistenFn } from "@tauri-apps/api/event";
+import {
+  ConsumerEvent,
+  ConsumerSession,
+  ConsumerNotification,
+  ConsumerSettingsFrom,
+  ConsumerSettingsTo,
+  ConsumerState,
+  ExportFormat,
+  ExportNotification,
+  KafkaRecord,
+  TopicSettings,
+} from "../models/kafka";
 import { addNotification } from "../providers";
 import { format, TauriError } from "./error";
 
@@ -14,15 +26,34 @@ export const stopConsumer = (clusterId: string, topic: string): Promise<void> =>
     addNotification({ type: "error", title: "Stop Kafka record", description: format(err) })
   );
 
-export const startConsumer = (clusterId: string, topic: string, offsetConfig: ConsumerSettingsFrom): Promise<void> =>
+export const startConsumer = (
+  clusterId: string,
+  topic: string,
+  offsetConfig: ConsumerSettingsFrom,
+  stopConfig?: ConsumerSettingsTo,
+  topicSettings?: TopicSettings,
+  resume?: boolean
+): Promise<void> =>
   invoke<void>("start_consumer", {
     clusterId,
     offsetConfig,
+    stopConfig,
+    topicSettings,
+    resume,
     topic,
   }).catch((err: TauriError) =>
     addNotification({ type: "error", title: "Start Kafka record", description: format(err) })
   );
 
+export const listenConsumerEvents = (
+  clusterId: string,
+  topic: string,
+  handler: (event: ConsumerEvent) => void
+): Promise<UnlistenFn> =>
+  listen<ConsumerNotification>("consumer", ({ payload }) => {
+    if (payload.clusterId == clusterId && payload.topic == topic) handler(payload.event);
+  });
+
 type GetRecordsPageResponse = {
   records: KafkaRecord[];
   nextPage?: number;
@@ -41,3 +72,48 @@ export const getRecordsPage = (
       throw err;
     }
   );
+
+export const searchRecords = (
+  clusterId: string,
+  topic: string,
+  pageNumber: number,
+  search: string
+): Promise<GetRecordsPageResponse> =>
+  invoke<GetRecordsPageResponse>("search_records", { clusterId, topic, search, pageNumber }).catch(
+    (err: TauriError) => {
+      addNotification({ type: "error", title: "Search Kafka records", description: format(err) });
+      throw err;
+    }
+  );
+
+export const exportRecords = (
+  clusterId: string,
+  topic: string,
+  exportFormat: ExportFormat,
+  outputPath: string,
+  query?: string
+): Promise<number> =>
+  invoke<number>("export_records", { clusterId, topic, query, format: exportFormat, outputPath }).catch((err: TauriError) => {
+    addNotification({ type: "error", title: "Export Kafka records", description: format(err) });
+    throw err;
+  });
+
+export const listenExportEvents = (
+  clusterId: string,
+  topic: string,
+  handler: (event: ExportNotification) => void
+): Promise<UnlistenFn> =>
+  listen<ExportNotification>("export", ({ payload }) => {
+    if (payload.clusterId == clusterId && payload.topic == topic) handler(payload);
+  });
+
+export const listSessions = (): Promise<ConsumerSession[]> =>
+  invoke<ConsumerSession[]>("list_sessions").catch((err: TauriError) => {
+    addNotification({ type: "error", title: "List consumer sessions", description: format(err) });
+    throw err;
+  });
+
+export const deleteSession = (clusterId: string, topic: string): Promise<void> =>
+  invoke<void>("delete_session", { clusterId, topic }).catch((err: TauriError) =>
+    addNotification({ type: "error", title: "Delete consumer session", description: format(err) })
+  );
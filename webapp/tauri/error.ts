@@ -1,9 +1,11 @@
 export type TauriError = {
   errorType: string;
   message: string;
+  position?: number; //position of the error in the user query
 };
 
-export const format = ({ errorType, message }: TauriError) => {
+export const format = ({ errorType, message, position }: TauriError) => {
+  if (errorType && message && position != undefined) return `${errorType}: ${message} (at position ${position})`;
   if (errorType && message) return `${errorType}: ${message}`;
   if (errorType) return `${errorType}`;
   if (message) return `${message}`;
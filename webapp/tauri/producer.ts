@@ -0,0 +1,34 @@
+import { invoke } from "@tauri-apps/api";
+import { ImportFormat, ImportResult, ProducerMode } from "../models/kafka";
+import { addNotification } from "../providers";
+import { format, TauriError } from "./error";
+
+export const produceRecord = (
+  clusterId: string,
+  topic: string,
+  key?: string,
+  payload?: string,
+  partition?: number,
+  timestamp?: number,
+  mode?: ProducerMode
+): Promise<void> =>
+  invoke<void>("produce_record", { clusterId, topic, key, payload, partition, timestamp, mode }).catch(
+    (err: TauriError) => {
+      addNotification({ type: "error", title: "Produce Kafka record", description: format(err) });
+      throw err;
+    }
+  );
+
+export const importRecords = (
+  clusterId: string,
+  topic: string,
+  filePath: string,
+  importFormat: ImportFormat,
+  mode?: ProducerMode
+): Promise<ImportResult> =>
+  invoke<ImportResult>("import_records", { clusterId, topic, filePath, format: importFormat, mode }).catch(
+    (err: TauriError) => {
+      addNotification({ type: "error", title: "Import Kafka records", description: format(err) });
+      throw err;
+    }
+  );